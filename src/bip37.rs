@@ -0,0 +1,303 @@
+use crate::format::{to_usize, Error};
+use std::f64::consts::LN_2;
+use std::io::{Read, Write};
+
+// max size of the filter data in bytes
+const MAX_BLOOM_FILTER_SIZE: usize = 36_000;
+// max number of hash functions
+const MAX_HASH_FUNCS: u32 = 50;
+// multiplier of the hash function index in the murmur3 seed
+const SEED_MULTIPLIER: u32 = 0xFBA4_C795;
+
+/// How a full node updates the filter when a transaction output matches it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Update {
+    None = 0,
+    All = 1,
+    P2PubkeyOnly = 2,
+}
+
+/// Bloom filter of [BIP37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki),
+/// the `CBloomFilter` of Bitcoin Core sent in `filterload` messages. The k
+/// hash functions are MurmurHash3 seeded with `i * 0xFBA4C795 + tweak`.
+pub struct Filter {
+    data: Vec<u8>,   // filter data, bit i is bit i % 8 of byte i / 8
+    hash_funcs: u32, // numbers of hash functions
+    tweak: u32,      // added to the seeds of hash functions
+    update: Update,  // nFlags
+}
+
+impl Filter {
+    /// Create a new BIP37 bloom filter structure, sized as Bitcoin Core does.
+    /// elements is an estimation of the maximum number of items to store.
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(elements: usize, fp_rate: f64, tweak: u32, update: Update) -> Self {
+        debug_assert!(elements > 0);
+        debug_assert!(fp_rate > 0.0 && fp_rate < 1.0);
+        let bits = (-1.0 / (LN_2 * LN_2) * elements as f64 * fp_rate.ln()) as usize;
+        let size = bits.min(MAX_BLOOM_FILTER_SIZE * 8) / 8;
+        // integer division before the multiplication, as Bitcoin Core does
+        let hash_funcs = ((size * 8 / elements) as f64 * LN_2) as u32;
+        Self {
+            data: vec![0; size],
+            hash_funcs: hash_funcs.min(MAX_HASH_FUNCS),
+            tweak,
+            update,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn hash_funcs(&self) -> u32 {
+        self.hash_funcs
+    }
+
+    pub fn tweak(&self) -> u32 {
+        self.tweak
+    }
+
+    pub fn update(&self) -> Update {
+        self.update
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        // an empty filter matches everything
+        if self.data.is_empty() {
+            return;
+        }
+        for i in 0..self.hash_funcs {
+            let index = self.hash(i, key);
+            self.data[index >> 3] |= 1 << (index & 7);
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        if self.data.is_empty() {
+            return true;
+        }
+        (0..self.hash_funcs).all(|i| {
+            let index = self.hash(i, key);
+            self.data[index >> 3] & (1 << (index & 7)) != 0
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.data.iter_mut().for_each(|x| *x = 0)
+    }
+
+    /// Writes the filter as serialized in a `filterload` message.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        write_compact_size(&mut writer, self.data.len() as u64)?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.hash_funcs.to_le_bytes())?;
+        writer.write_all(&self.tweak.to_le_bytes())?;
+        writer.write_all(&[self.update as u8])?;
+        Ok(())
+    }
+
+    /// Reads a filter serialized in a `filterload` message, filters larger
+    /// than 36,000 bytes or with more than 50 hash functions are rejected.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let size = to_usize(read_compact_size(&mut reader)?)?;
+        if size > MAX_BLOOM_FILTER_SIZE {
+            return Err(Error::Inconsistent("filter size exceeds 36,000 bytes"));
+        }
+        let mut data = vec![0; size];
+        reader.read_exact(&mut data)?;
+        let mut buf = [0; 9];
+        reader.read_exact(&mut buf)?;
+        let hash_funcs = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let tweak = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        if hash_funcs > MAX_HASH_FUNCS {
+            return Err(Error::Inconsistent("hash functions exceed 50"));
+        }
+        let update = match buf[8] {
+            0 => Update::None,
+            1 => Update::All,
+            2 => Update::P2PubkeyOnly,
+            _ => return Err(Error::Inconsistent("unknown update flags")),
+        };
+        Ok(Self {
+            data,
+            hash_funcs,
+            tweak,
+            update,
+        })
+    }
+
+    fn hash(&self, i: u32, key: &[u8]) -> usize {
+        let seed = i.wrapping_mul(SEED_MULTIPLIER).wrapping_add(self.tweak);
+        murmur3(seed, key) as usize % (self.data.len() * 8)
+    }
+}
+
+fn write_compact_size<W: Write>(writer: &mut W, size: u64) -> Result<(), Error> {
+    if size < 0xfd {
+        writer.write_all(&[size as u8])?;
+    } else if size <= 0xffff {
+        writer.write_all(&[0xfd])?;
+        writer.write_all(&(size as u16).to_le_bytes())?;
+    } else if size <= 0xffff_ffff {
+        writer.write_all(&[0xfe])?;
+        writer.write_all(&(size as u32).to_le_bytes())?;
+    } else {
+        writer.write_all(&[0xff])?;
+        writer.write_all(&size.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_compact_size<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first)?;
+    let (len, min) = match first[0] {
+        0xfd => (2, 0xfd),
+        0xfe => (4, 0x1_0000),
+        0xff => (8, 0x1_0000_0000),
+        size => return Ok(u64::from(size)),
+    };
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf[..len])?;
+    let size = u64::from_le_bytes(buf);
+    if size < min {
+        return Err(Error::Inconsistent("non-canonical compact size"));
+    }
+    Ok(size)
+}
+
+// MurmurHash3 x86 32 bits
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut chunks = data.chunks_exact(4);
+    let mut h = chunks.by_ref().fold(seed, |h, chunk| {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        (h ^ mix(k)).rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64)
+    });
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail.iter().rev().fold(0, |k, byte| k << 8 | u32::from(*byte));
+        h ^= mix(k);
+    }
+
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len() / 2)
+            .map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn _contains(items: &[Vec<u8>]) {
+        let mut filter = Filter::new(100, 0.01, random(), Update::All);
+        items.iter().for_each(|i| filter.insert(i));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<Vec<u8>>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    // from hash_tests.cpp of Bitcoin Core
+    #[test]
+    fn murmur3_vectors() {
+        let vectors = [
+            (0x0000_0000, 0x0000_0000, ""),
+            (0x6a39_6f08, 0xFBA4_C795, ""),
+            (0x81f1_6f39, 0xffff_ffff, ""),
+            (0x514e_28b7, 0x0000_0000, "00"),
+            (0xea3f_0b17, 0xFBA4_C795, "00"),
+            (0xfd6c_f10d, 0x0000_0000, "ff"),
+            (0x16c6_b7ab, 0x0000_0000, "0011"),
+            (0x8eb5_1c3d, 0x0000_0000, "001122"),
+            (0xb447_1bf8, 0x0000_0000, "00112233"),
+            (0xe230_1fa8, 0x0000_0000, "0011223344"),
+            (0xfc2e_4a15, 0x0000_0000, "001122334455"),
+            (0xb074_502c, 0x0000_0000, "00112233445566"),
+            (0x8034_d2a0, 0x0000_0000, "0011223344556677"),
+            (0xb469_8def, 0x0000_0000, "001122334455667788"),
+        ];
+        for (expected, seed, data) in vectors.iter() {
+            assert_eq!(*expected, murmur3(*seed, &hex(data)));
+        }
+    }
+
+    // from bloom_tests.cpp of Bitcoin Core
+    #[test]
+    fn serialize_vectors() {
+        for (tweak, expected) in [
+            (0, "03614e9b050000000000000001"),
+            (2_147_483_649, "03ce4299050000000100008001"),
+        ]
+        .iter()
+        {
+            let mut filter = Filter::new(3, 0.01, *tweak, Update::All);
+            filter.insert(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8"));
+            assert!(filter.contains(&hex("99108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            // one bit different in first byte
+            assert!(!filter.contains(&hex("19108ad8ed9bb6274d3980bab5a85c048f0950c8")));
+            filter.insert(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee"));
+            assert!(filter.contains(&hex("b5a2c786d9ef4658287ced5914b37a1b4aa32eee")));
+            filter.insert(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5"));
+            assert!(filter.contains(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5")));
+
+            let mut bytes = Vec::new();
+            filter.write_to(&mut bytes).unwrap();
+            assert_eq!(hex(expected), bytes);
+
+            let filter = Filter::read_from(&bytes[..]).unwrap();
+            assert_eq!(
+                (5, *tweak, Update::All),
+                (filter.hash_funcs(), filter.tweak(), filter.update())
+            );
+            assert!(filter.contains(&hex("b9300670b4c5366e95b2699e8b18bc75e5f729c5")));
+        }
+    }
+
+    #[test]
+    fn size_limits() {
+        let filter = Filter::new(1_000_000, 0.000_001, 0, Update::None);
+        assert_eq!(MAX_BLOOM_FILTER_SIZE, filter.data().len());
+        assert_eq!(MAX_HASH_FUNCS, Filter::new(1, 1e-20, 0, Update::None).hash_funcs());
+
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        assert_eq!(&[0xfd, 0xa0, 0x8c], &bytes[..3]);
+        assert!(Filter::read_from(&bytes[..]).is_ok());
+        assert!(matches!(
+            Filter::read_from(&[0xfd, 0x03, 0x00][..]),
+            Err(Error::Inconsistent(_))
+        ));
+        assert!(matches!(
+            Filter::read_from(&[0xfd, 0xa1, 0x8c][..]),
+            Err(Error::Inconsistent(_))
+        ));
+        assert!(matches!(
+            Filter::read_from(&[0, 51, 0, 0, 0, 0, 0, 0, 0, 0][..]),
+            Err(Error::Inconsistent(_))
+        ));
+        // an empty filter matches everything
+        assert!(Filter::read_from(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0][..])
+            .unwrap()
+            .contains(b"any"));
+    }
+}
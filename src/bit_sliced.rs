@@ -0,0 +1,131 @@
+use crate::buckets::Buckets;
+use crate::{BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+type Word = u64;
+const BITS_PER_WORD: usize = 64;
+
+/// A bit-sliced set of classic bloom filters: row i stores bucket i of every
+/// filter contiguously, so a query ANDs k rows and yields the bitmap of the
+/// filters which may contain the item.
+///
+/// All filters must be created with the same buckets count, k and hash kernels.
+pub struct FilterSet<BHK: BuildHashKernels> {
+    rows: Vec<Vec<Word>>,  // one row of len bits per bucket
+    len: usize,            // number of filters
+    bucket_count: usize,   // buckets count of filters
+    hash_kernels: BHK::HK, // hash kernels shared by all filters
+}
+
+impl<BHK: BuildHashKernels> FilterSet<BHK> {
+    /// Create a new set of classic bloom filters with bucket_count buckets and k hash functions.
+    pub fn new(bucket_count: usize, k: usize, build_hash_kernels: BHK) -> Self {
+        Self {
+            rows: (0..bucket_count).map(|_| Vec::new()).collect(),
+            len: 0,
+            bucket_count,
+            hash_kernels: build_hash_kernels.with_k(k, bucket_count),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends the `raw_data` of a filter, returns the id of the filter.
+    pub fn push(&mut self, raw_data: &[u8]) -> usize {
+        let buckets = Buckets::with_raw_data(self.bucket_count, 1, raw_data);
+        let id = self.len;
+        let (word, bit) = (id / BITS_PER_WORD, id % BITS_PER_WORD);
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            if bit == 0 {
+                row.push(0);
+            }
+            row[word] |= (buckets.get(i) as Word) << bit;
+        }
+        self.len += 1;
+        id
+    }
+
+    /// Returns the bitmap of the filters which may contain item, bit i of the
+    /// result is set if filter i may contain item.
+    pub fn bitmap<T: Hash>(&self, item: &T) -> Vec<Word> {
+        let mut result = vec![Word::MAX; (self.len + BITS_PER_WORD - 1) / BITS_PER_WORD];
+        for i in self.hash_kernels.hash_iter(item) {
+            result
+                .iter_mut()
+                .zip(self.rows[i].iter())
+                .for_each(|(acc, word)| *acc &= *word);
+        }
+        result
+    }
+
+    /// Returns the ids of the filters which may contain item.
+    pub fn matches<T: Hash>(&self, item: &T) -> Vec<usize> {
+        let mut result = Vec::new();
+        for (index, word) in self.bitmap(item).into_iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                let id = index * BITS_PER_WORD + word.trailing_zeros() as usize;
+                if id < self.len {
+                    result.push(id);
+                }
+                word &= word - 1;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic::Filter as ClassicFilter;
+    use crate::hash::{DefaultBuildHashKernels, DefaultBuildHasher};
+    use crate::BloomFilter;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+
+    const BYTES: usize = 64;
+    const K: usize = 3;
+
+    fn filter(hash_seed: usize, items: &[usize]) -> Vec<u8> {
+        let build_hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher);
+        let mut filter = ClassicFilter::with_raw_data(&[0; BYTES], K, build_hash_kernels);
+        items.iter().for_each(|i| filter.insert(i));
+        filter.buckets().raw_data()
+    }
+
+    fn _matches(items: &[usize]) {
+        let hash_seed = random();
+        let mut set = FilterSet::new(BYTES * 8, K, DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher));
+        let ids = items.iter().map(|i| set.push(&filter(hash_seed, &[*i]))).collect::<Vec<_>>();
+        assert_eq!(items.len(), set.len());
+        assert!(items.iter().zip(ids).all(|(i, id)| set.matches(i).contains(&id)));
+    }
+
+    proptest! {
+        #[test]
+        fn matches(ref items in any_with::<Vec<usize>>(size_range(200).lift())) {
+            _matches(items)
+        }
+    }
+
+    #[test]
+    fn bitmap() {
+        let hash_seed = random();
+        let mut set = FilterSet::new(BYTES * 8, K, DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher));
+        assert!(set.is_empty());
+        assert!(set.matches(&1usize).is_empty());
+        set.push(&filter(hash_seed, &[1, 2]));
+        set.push(&filter(hash_seed, &[2, 3]));
+        set.push(&filter(hash_seed, &[3, 1]));
+        assert_eq!(0b101, set.bitmap(&1usize)[0]);
+        assert_eq!(vec![0, 1], set.matches(&2usize));
+        assert_eq!(vec![1, 2], set.matches(&3usize));
+    }
+}
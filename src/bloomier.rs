@@ -0,0 +1,220 @@
+use crate::buckets::Buckets;
+use crate::format::{to_usize, Error};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem::size_of;
+
+// a 3-hypergraph with 1.23 * n vertices can be peeled with high probability
+const LOAD_FACTOR: f64 = 1.23;
+const MAX_ATTEMPTS: u64 = 100;
+const HEADER_SIZE: usize = 2 * size_of::<u64>() + 1;
+
+/// A static retrieval structure mapping a fixed key set to small values,
+/// built on a 3-wise XOR hypergraph: the value of a key is the XOR of the
+/// buckets at its 3 positions. Non-member keys return arbitrary values.
+pub struct Filter<BH: BuildHasher> {
+    buckets: Buckets,      // 3 segments of segment_length buckets of value_bits
+    segment_length: usize, // buckets per segment
+    value_bits: u8,        // width of values
+    seed: u64,             // seed of the successful construction attempt
+    build_hasher: BH,
+}
+
+impl<BH: BuildHasher> Filter<BH> {
+    /// Builds the structure from pairs of distinct keys and values,
+    /// value_bits is the width of values, in [1, 7].
+    pub fn new<K: Hash>(pairs: &[(K, u8)], value_bits: u8, hash_seed: usize, build_hasher: BH) -> Self {
+        debug_assert!(value_bits > 0 && value_bits < 8);
+        debug_assert!(pairs.iter().all(|(_, value)| *value < 1 << value_bits));
+        let segment_length = ((pairs.len() as f64 * LOAD_FACTOR).ceil() as usize + 32) / 3;
+        let mut filter = Self {
+            buckets: Buckets::new(3 * segment_length, value_bits),
+            segment_length,
+            value_bits,
+            seed: hash_seed as u64,
+            build_hasher,
+        };
+
+        for attempt in 0..MAX_ATTEMPTS {
+            filter.seed = (hash_seed as u64).wrapping_add(attempt);
+            let positions = pairs.iter().map(|(key, _)| filter.positions(key)).collect::<Vec<_>>();
+            if let Some(order) = peel(&positions, filter.buckets.len()) {
+                for (index, bucket) in order.into_iter().rev() {
+                    let value = positions[index]
+                        .iter()
+                        .fold(pairs[index].1, |acc, i| acc ^ filter.buckets.get(*i));
+                    filter.buckets.set(bucket, value);
+                }
+                return filter;
+            }
+        }
+        panic!("keys must be distinct");
+    }
+
+    /// Restores the structure from its `raw_data`, which header and buckets
+    /// length are checked.
+    pub fn with_raw_data(raw_data: &[u8], build_hasher: BH) -> Result<Self, Error> {
+        if raw_data.len() < HEADER_SIZE {
+            return Err(Error::Inconsistent("raw data is shorter than the header"));
+        }
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&raw_data[0..8]);
+        let seed = u64::from_le_bytes(word);
+        word.copy_from_slice(&raw_data[8..16]);
+        let segment_length = to_usize(u64::from_le_bytes(word))?;
+        let value_bits = raw_data[16];
+        if value_bits == 0 || value_bits >= 8 || segment_length == 0 {
+            return Err(Error::Inconsistent(
+                "value bits must be in [1, 7] and segment length positive",
+            ));
+        }
+        let len = segment_length
+            .checked_mul(3 * value_bits as usize)
+            .map(|bits| (bits + 63) / 64 * 8);
+        if len != Some(raw_data.len() - HEADER_SIZE) {
+            return Err(Error::Inconsistent("buckets length doesn't match the segment length"));
+        }
+        Ok(Self {
+            buckets: Buckets::with_raw_data(3 * segment_length, value_bits, &raw_data[HEADER_SIZE..]),
+            segment_length,
+            value_bits,
+            seed,
+            build_hasher,
+        })
+    }
+
+    /// Returns the seed, the segment length as little endian u64 and the value
+    /// width as u8, followed by the buckets raw data.
+    pub fn raw_data(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(HEADER_SIZE);
+        result.extend_from_slice(&self.seed.to_le_bytes());
+        result.extend_from_slice(&(self.segment_length as u64).to_le_bytes());
+        result.push(self.value_bits);
+        result.extend(self.buckets.raw_data());
+        result
+    }
+
+    pub fn value_bits(&self) -> u8 {
+        self.value_bits
+    }
+
+    /// Returns the value of a key of the construction set, or an arbitrary value for other keys.
+    pub fn get<K: Hash>(&self, key: &K) -> u8 {
+        self.positions(key).iter().fold(0, |acc, i| acc ^ self.buckets.get(*i))
+    }
+
+    fn positions<K: Hash>(&self, key: &K) -> [usize; 3] {
+        let hasher = &mut self.build_hasher.build_hasher();
+        (self.seed, key).hash(hasher);
+        let hash = hasher.finish();
+        let reduce = |h: u64| ((h as u32 as u64 * self.segment_length as u64) >> 32) as usize;
+        [
+            reduce(hash),
+            self.segment_length + reduce(hash.rotate_left(21)),
+            2 * self.segment_length + reduce(hash.rotate_left(42)),
+        ]
+    }
+}
+
+// returns the peeling order as pairs of key index and the bucket assigned to
+// that key, or `None` if the hypergraph has a cycle
+fn peel(positions: &[[usize; 3]], buckets_count: usize) -> Option<Vec<(usize, usize)>> {
+    let mut counts = vec![0u32; buckets_count];
+    let mut xors = vec![0usize; buckets_count];
+    for (index, keys) in positions.iter().enumerate() {
+        for i in keys {
+            counts[*i] += 1;
+            xors[*i] ^= index;
+        }
+    }
+
+    let mut queue = (0..buckets_count).filter(|i| counts[*i] == 1).collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(positions.len());
+    while let Some(bucket) = queue.pop() {
+        if counts[bucket] != 1 {
+            continue;
+        }
+        let index = xors[bucket];
+        order.push((index, bucket));
+        for i in &positions[index] {
+            counts[*i] -= 1;
+            xors[*i] ^= index;
+            if counts[*i] == 1 {
+                queue.push(*i);
+            }
+        }
+    }
+
+    if order.len() == positions.len() {
+        Some(order)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHasher;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::HashMap;
+
+    // keeps the last value of duplicated keys
+    fn distinct(pairs: &[(usize, u8)], value_bits: u8) -> Vec<(usize, u8)> {
+        let pairs = pairs.iter().cloned().collect::<HashMap<_, _>>();
+        pairs.into_iter().map(|(k, v)| (k, v & ((1 << value_bits) - 1))).collect()
+    }
+
+    fn _get(pairs: &[(usize, u8)]) {
+        let pairs = distinct(pairs, 4);
+        let filter = Filter::new(&pairs, 4, random(), DefaultBuildHasher);
+        assert!(pairs.iter().all(|(k, v)| filter.get(k) == *v));
+    }
+
+    proptest! {
+        #[test]
+        fn get(ref pairs in any_with::<Vec<(usize, u8)>>(size_range(256).lift())) {
+            _get(pairs)
+        }
+    }
+
+    fn _raw_data(pairs: &[(usize, u8)]) {
+        let pairs = distinct(pairs, 3);
+        let filter = Filter::new(&pairs, 3, random(), DefaultBuildHasher);
+        let filter = Filter::with_raw_data(&filter.raw_data(), DefaultBuildHasher).unwrap();
+        assert_eq!(3, filter.value_bits());
+        assert!(pairs.iter().all(|(k, v)| filter.get(k) == *v));
+    }
+
+    proptest! {
+        #[test]
+        fn raw_data(ref pairs in any_with::<Vec<(usize, u8)>>(size_range(256).lift())) {
+            _raw_data(pairs)
+        }
+    }
+
+    #[test]
+    fn raw_data_errors() {
+        let pairs = (0..100usize).map(|i| (i, (i % 8) as u8)).collect::<Vec<_>>();
+        let bytes = Filter::new(&pairs, 3, random(), DefaultBuildHasher).raw_data();
+        let restore = |bytes: &[u8]| Filter::with_raw_data(bytes, DefaultBuildHasher);
+        assert!(restore(&bytes).is_ok());
+        assert!(matches!(restore(&bytes[..HEADER_SIZE - 1]), Err(Error::Inconsistent(_))));
+        assert!(matches!(restore(&bytes[..bytes.len() - 8]), Err(Error::Inconsistent(_))));
+        let mut corrupted = bytes.clone();
+        corrupted[16] = 8;
+        assert!(matches!(restore(&corrupted), Err(Error::Inconsistent(_))));
+        let mut corrupted = bytes;
+        corrupted[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(restore(&corrupted), Err(Error::Inconsistent(_))));
+    }
+
+    #[test]
+    fn bits_per_key() {
+        let pairs = (0..10_000usize).map(|i| (i, (i % 16) as u8)).collect::<Vec<_>>();
+        let filter = Filter::new(&pairs, 4, random(), DefaultBuildHasher);
+        assert!(pairs.iter().all(|(k, v)| filter.get(k) == *v));
+        // 1.23 * 4 bits per key
+        assert!(filter.raw_data().len() * 8 < 10_000 * 5);
+    }
+}
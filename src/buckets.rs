@@ -1,13 +1,60 @@
+#[cfg(feature = "mmap")]
+use crate::format::HEADER_LEN;
+#[cfg(feature = "mmap")]
+use memmap2::MmapMut;
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use std::f64::consts::LN_2;
+#[cfg(feature = "mmap")]
+use std::io;
 use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
 use std::ptr::copy_nonoverlapping;
+#[cfg(feature = "mmap")]
+use std::slice;
 
 type Word = u64;
 const BYTES_PER_WORD: usize = size_of::<Word>();
 const BITS_PER_WORD: usize = BYTES_PER_WORD * 8;
 
+// words of the buckets, in memory or in a memory-mapped file after its header
+enum Storage {
+    Memory(Vec<Word>),
+    #[cfg(feature = "mmap")]
+    Mapped(MmapMut),
+}
+
+impl Deref for Storage {
+    type Target = [Word];
+
+    fn deref(&self) -> &[Word] {
+        match self {
+            Storage::Memory(data) => data,
+            // safety: the map is page aligned and HEADER_LEN a multiple of the word size
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(mmap) => unsafe {
+                let words = (mmap.len() - HEADER_LEN) / BYTES_PER_WORD;
+                slice::from_raw_parts(mmap[HEADER_LEN..].as_ptr() as *const Word, words)
+            },
+        }
+    }
+}
+
+impl DerefMut for Storage {
+    fn deref_mut(&mut self) -> &mut [Word] {
+        match self {
+            Storage::Memory(data) => data,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(mmap) => unsafe {
+                let words = (mmap.len() - HEADER_LEN) / BYTES_PER_WORD;
+                slice::from_raw_parts_mut(mmap[HEADER_LEN..].as_mut_ptr() as *mut Word, words)
+            },
+        }
+    }
+}
+
 pub struct Buckets {
-    data: Vec<Word>,
+    data: Storage,
     count: usize,
     bucket_size: u8,
     max: u8,
@@ -23,7 +70,7 @@ impl Buckets {
     pub fn new(count: usize, bucket_size: u8) -> Self {
         debug_assert!(bucket_size < 8);
         Self {
-            data: vec![0; (count * bucket_size as usize + BITS_PER_WORD - 1) / BITS_PER_WORD],
+            data: Storage::Memory(vec![0; (count * bucket_size as usize + BITS_PER_WORD - 1) / BITS_PER_WORD]),
             count,
             bucket_size,
             max: (1u8 << bucket_size) - 1,
@@ -46,13 +93,36 @@ impl Buckets {
             .collect::<Vec<_>>();
 
         Self {
-            data,
+            data: Storage::Memory(data),
+            count,
+            bucket_size,
+            max: (1u8 << bucket_size) - 1,
+        }
+    }
+
+    /// Uses the words of a file created or opened by `format::create_mmap`
+    /// or `format::open_mmap`.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn with_mmap(count: usize, bucket_size: u8, mmap: MmapMut) -> Self {
+        debug_assert!(bucket_size < 8);
+        debug_assert!((count * bucket_size as usize + BITS_PER_WORD - 1) / BITS_PER_WORD * 8 == mmap.len() - HEADER_LEN);
+        Self {
+            data: Storage::Mapped(mmap),
             count,
             bucket_size,
             max: (1u8 << bucket_size) - 1,
         }
     }
 
+    /// Writes the modified buckets to their memory-mapped file, if any.
+    #[cfg(feature = "mmap")]
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.data {
+            Storage::Memory(_) => Ok(()),
+            Storage::Mapped(mmap) => mmap.flush(),
+        }
+    }
+
     pub fn raw_data(&self) -> Vec<u8> {
         let mut result = vec![0; self.data.len() * BYTES_PER_WORD];
         for (d, chunk) in self.data.iter().zip(result.chunks_mut(BYTES_PER_WORD)) {
@@ -65,18 +135,20 @@ impl Buckets {
     }
 
     pub fn update(&mut self, raw_data: &[u8]) {
-        let new_data = self
-            .data
-            .iter()
+        self.data
+            .iter_mut()
             .zip(raw_data.chunks(BYTES_PER_WORD))
-            .map(|(word, bytes)| {
-                bytes.iter().enumerate().fold(*word, |acc, (offset, byte)| {
+            .for_each(|(word, bytes)| {
+                *word = bytes.iter().enumerate().fold(*word, |acc, (offset, byte)| {
                     acc | (*byte as Word) << (offset * BYTES_PER_WORD)
                 })
-            })
-            .collect::<Vec<_>>();
+            });
+    }
 
-        self.data = new_data;
+    /// Merges the buckets of other via `BitOr` operation, both must have the same size.
+    pub fn union(&mut self, other: &Self) {
+        debug_assert!(self.count == other.count && self.bucket_size == other.bucket_size);
+        self.data.iter_mut().zip(other.data.iter()).for_each(|(a, b)| *a |= *b)
     }
 
     #[inline(always)]
@@ -89,6 +161,11 @@ impl Buckets {
         self.max
     }
 
+    #[inline(always)]
+    pub fn bucket_size(&self) -> u8 {
+        self.bucket_size
+    }
+
     pub fn reset(&mut self) {
         self.data.iter_mut().for_each(|x| *x = 0)
     }
@@ -144,11 +221,105 @@ impl Buckets {
     }
 }
 
+/// Read-only buckets borrowing the `raw_data` of `Buckets`, the bits are read
+/// byte by byte so the data doesn't need to be aligned.
+pub struct BucketsRef<'a> {
+    data: &'a [u8],
+    count: usize,
+    bucket_size: u8,
+    max: u8,
+}
+
+impl<'a> BucketsRef<'a> {
+    pub fn with_raw_data(count: usize, bucket_size: u8, raw_data: &'a [u8]) -> Self {
+        debug_assert!(bucket_size < 8);
+        debug_assert!((count * bucket_size as usize + BITS_PER_WORD - 1) / BITS_PER_WORD * 8 == raw_data.len());
+        Self {
+            data: raw_data,
+            count,
+            bucket_size,
+            max: (1u8 << bucket_size) - 1,
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    #[inline(always)]
+    pub fn max_value(&self) -> u8 {
+        self.max
+    }
+
+    #[inline(always)]
+    pub fn bucket_size(&self) -> u8 {
+        self.bucket_size
+    }
+
+    pub fn get(&self, bucket: usize) -> u8 {
+        // words are little-endian, so bit i is bit i % 8 of byte i / 8
+        let offset = bucket * self.bucket_size as usize;
+        let index = offset / 8;
+        let mut bits = u16::from(self.data[index]);
+        // a bucket spans at most 2 bytes
+        if offset % 8 + self.bucket_size as usize > 8 {
+            bits |= u16::from(self.data[index + 1]) << 8;
+        }
+        (bits >> (offset % 8)) as u8 & self.max
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Buckets {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Buckets", 3)?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field("bucket_size", &self.bucket_size)?;
+        state.serialize_field("data", &*self.data)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Buckets {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Buckets")]
+        struct Data {
+            count: usize,
+            bucket_size: u8,
+            data: Vec<Word>,
+        }
+
+        let Data {
+            count,
+            bucket_size,
+            data,
+        } = Data::deserialize(deserializer)?;
+        if bucket_size == 0 || bucket_size >= 8 {
+            return Err(D::Error::custom("bucket size must be in [1, 7]"));
+        }
+        let words = count
+            .checked_mul(bucket_size as usize)
+            .map(|bits| (bits + BITS_PER_WORD - 1) / BITS_PER_WORD);
+        if words != Some(data.len()) {
+            return Err(D::Error::custom("buckets data length doesn't match the buckets count"));
+        }
+        Ok(Self {
+            data: Storage::Memory(data),
+            count,
+            bucket_size,
+            max: (1u8 << bucket_size) - 1,
+        })
+    }
+}
+
 const LN_2_2: f64 = LN_2 * LN_2;
 
 // Calculates the optimal buckets count, m, based on the number of
 // items and the desired rate of false positives.
-fn compute_m_num(items_count: usize, fp_rate: f64) -> usize {
+pub(crate) fn compute_m_num(items_count: usize, fp_rate: f64) -> usize {
     debug_assert!(items_count > 0);
     debug_assert!(fp_rate > 0.0 && fp_rate < 1.0);
     ((items_count as f64) * fp_rate.ln().abs() / LN_2_2).ceil() as usize
@@ -250,6 +421,21 @@ mod tests {
         assert_eq!(6, buckets.get(21));
     }
 
+    #[test]
+    fn buckets_ref() {
+        for bucket_size in 1..8 {
+            let mut buckets = Buckets::new(100, bucket_size);
+            (0..100).for_each(|i| buckets.set(i, (i * 37 % 128) as u8));
+            // an unaligned copy of the raw data
+            let mut bytes = vec![0];
+            bytes.extend_from_slice(&buckets.raw_data());
+            let buckets_ref = BucketsRef::with_raw_data(100, bucket_size, &bytes[1..]);
+            assert_eq!((100, bucket_size), (buckets_ref.len(), buckets_ref.bucket_size()));
+            assert_eq!(buckets.max_value(), buckets_ref.max_value());
+            assert!((0..100).all(|i| buckets.get(i) == buckets_ref.get(i)));
+        }
+    }
+
     #[test]
     fn update() {
         let mut b1 = Buckets::new(100, 1);
@@ -271,4 +457,41 @@ mod tests {
         assert_eq!(1, b1.get(49));
         assert_eq!(1, b1.get(63));
     }
+
+    #[test]
+    fn union() {
+        let mut b1 = Buckets::new(100, 1);
+        b1.set(0, 1);
+        b1.set(99, 1);
+
+        let mut b2 = Buckets::new(100, 1);
+        b2.set(20, 1);
+        b2.set(64, 1);
+
+        b1.union(&b2);
+        assert_eq!(1, b1.get(0));
+        assert_eq!(1, b1.get(20));
+        assert_eq!(1, b1.get(64));
+        assert_eq!(1, b1.get(99));
+        assert_eq!(0, b1.get(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let mut buckets = Buckets::new(100, 3);
+        buckets.set(0, 1);
+        buckets.set(21, 6);
+        buckets.set(99, 7);
+        let json = serde_json::to_string(&buckets).unwrap();
+        let buckets: Buckets = serde_json::from_str(&json).unwrap();
+        assert_eq!(100, buckets.len());
+        assert_eq!(7, buckets.max_value());
+        assert_eq!(1, buckets.get(0));
+        assert_eq!(6, buckets.get(21));
+        assert_eq!(7, buckets.get(99));
+
+        assert!(serde_json::from_str::<Buckets>(r#"{"count":100,"bucket_size":3,"data":[0,0]}"#).is_err());
+        assert!(serde_json::from_str::<Buckets>(r#"{"count":64,"bucket_size":8,"data":[0]}"#).is_err());
+    }
 }
@@ -0,0 +1,163 @@
+use crate::buckets::compute_m_num;
+use crate::classic::Filter as ClassicFilter;
+use crate::hash::compute_k_num;
+use crate::{BloomFilter, DefaultBuildHashKernels};
+use std::hash::{BuildHasher, Hash};
+
+// a cascade deeper than this means the include and exclude sets are not disjoint,
+// or fp_rate is too high for the levels to shrink fast enough
+const MAX_LEVELS: usize = 64;
+
+type Level<BH> = ClassicFilter<DefaultBuildHashKernels<BH>>;
+
+/// A Bloom filter cascade encodes an include set against a known exclude set
+/// with no false positives: every level stores the false positives of the
+/// level before it, until a level produces none.
+pub struct Filter<BH: BuildHasher + Clone> {
+    levels: Vec<Level<BH>>, // classic filters, level 0 stores the include set
+    k: usize,               // numbers of hash iterating of each level
+    hash_seed: usize,       // seed of level 0, level i uses hash_seed + i
+    build_hasher: BH,
+}
+
+impl<BH: BuildHasher + Clone> Filter<BH> {
+    /// Builds a cascade answering `true` for every item of include and `false`
+    /// for every item of exclude, the two sets must be disjoint.
+    /// fp_rate is the wanted rate of false positives of each level, in ]0.0, 1.0[
+    pub fn new<T: Hash>(include: &[T], exclude: &[T], fp_rate: f64, hash_seed: usize, build_hasher: BH) -> Self {
+        assert!(fp_rate > 0.0 && fp_rate < 1.0, "fp_rate must be in ]0.0, 1.0[");
+        let mut filter = Self {
+            levels: Vec::new(),
+            k: compute_k_num(fp_rate),
+            hash_seed,
+            build_hasher,
+        };
+        let mut inserts = include.iter().collect::<Vec<_>>();
+        let mut checks = exclude.iter().collect::<Vec<_>>();
+        while !inserts.is_empty() {
+            let level = filter.levels.len();
+            assert!(
+                level < MAX_LEVELS,
+                "cascade exceeds {} levels: include and exclude sets must be disjoint, or fp_rate is too high",
+                MAX_LEVELS
+            );
+
+            let words = (compute_m_num(inserts.len(), fp_rate) + 63) / 64;
+            let mut current = filter.new_level(level, &vec![0; words * 8]);
+            inserts.iter().for_each(|item| current.insert(&(level, item)));
+            let false_positives = checks
+                .into_iter()
+                .filter(|item| current.contains(&(level, item)))
+                .collect::<Vec<_>>();
+
+            filter.levels.push(current);
+            checks = inserts;
+            inserts = false_positives;
+        }
+        filter
+    }
+
+    /// Restores a cascade from the `raw_data` of each level.
+    pub fn with_raw_data(levels: &[Vec<u8>], k: usize, hash_seed: usize, build_hasher: BH) -> Self {
+        let mut filter = Self {
+            levels: Vec::with_capacity(levels.len()),
+            k,
+            hash_seed,
+            build_hasher,
+        };
+        for (level, raw_data) in levels.iter().enumerate() {
+            let current = filter.new_level(level, raw_data);
+            filter.levels.push(current);
+        }
+        filter
+    }
+
+    /// Returns the buckets raw data of each level, starting from level 0.
+    pub fn raw_data(&self) -> Vec<Vec<u8>> {
+        self.levels.iter().map(|level| level.buckets().raw_data()).collect()
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn contains<T: Hash>(&self, item: &T) -> bool {
+        match self
+            .levels
+            .iter()
+            .enumerate()
+            .position(|(level, filter)| !filter.contains(&(level, item)))
+        {
+            // rejected by an even level means the item is not in the include set
+            Some(level) => level % 2 == 1,
+            None => self.levels.len() % 2 == 1,
+        }
+    }
+
+    fn new_level(&self, level: usize, raw_data: &[u8]) -> Level<BH> {
+        let build_hash_kernels = DefaultBuildHashKernels::new(self.hash_seed.wrapping_add(level), self.build_hasher.clone());
+        ClassicFilter::with_raw_data(raw_data, self.k, build_hash_kernels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHasher;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::HashSet;
+
+    fn split(items: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let items = items.iter().cloned().collect::<HashSet<_>>().into_iter().collect::<Vec<_>>();
+        let (include, exclude) = items.split_at(items.len() / 4);
+        (include.to_vec(), exclude.to_vec())
+    }
+
+    fn _contains(items: &[usize]) {
+        let (include, exclude) = split(items);
+        let filter = Filter::new(&include, &exclude, 0.5, random(), DefaultBuildHasher);
+        assert!(include.iter().all(|i| filter.contains(i)));
+        assert!(exclude.iter().all(|i| !filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<usize>>(size_range(512).lift())) {
+            _contains(items)
+        }
+    }
+
+    fn _raw_data(items: &[usize]) {
+        let (include, exclude) = split(items);
+        let hash_seed = random();
+        let filter = Filter::new(&include, &exclude, 0.5, hash_seed, DefaultBuildHasher);
+        let filter = Filter::with_raw_data(&filter.raw_data(), filter.k(), hash_seed, DefaultBuildHasher);
+        assert!(include.iter().all(|i| filter.contains(i)));
+        assert!(exclude.iter().all(|i| !filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn raw_data(ref items in any_with::<Vec<usize>>(size_range(512).lift())) {
+            _raw_data(items)
+        }
+    }
+
+    #[test]
+    fn empty_include() {
+        let filter = Filter::new::<usize>(&[], &[1, 2, 3], 0.03, random(), DefaultBuildHasher);
+        assert_eq!(0, filter.levels());
+        assert!(!filter.contains(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "fp_rate must be in ]0.0, 1.0[")]
+    fn fp_rate_out_of_range() {
+        Filter::new(&[1usize], &[2], 1.0, random(), DefaultBuildHasher);
+    }
+}
@@ -1,10 +1,25 @@
-use crate::buckets::Buckets;
+#[cfg(feature = "mmap")]
+use crate::buckets::compute_m_num;
+use crate::buckets::{Buckets, BucketsRef};
+use crate::format::{self, Header, CLASSIC, DEFAULT_HASH_KERNELS};
+use crate::go::{self, BuildFnv1Hasher};
+#[cfg(feature = "serde")]
+use crate::hash::DefaultHashKernels;
+use crate::hash::{DefaultBuildHashKernels, DefaultBuildHasher};
 use crate::{BloomFilter, BuildHashKernels, HashKernels, UpdatableBloomFilter};
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::io::{Read, Write};
+#[cfg(feature = "mmap")]
+use std::path::Path;
 
 pub struct Filter<BHK: BuildHashKernels> {
     buckets: Buckets,      // filter data
     hash_kernels: BHK::HK, // hash kernels
+    count: usize,          // number of inserts, the count of Go BoomFilters
 }
 
 impl<BHK: BuildHashKernels> Filter<BHK> {
@@ -14,23 +29,161 @@ impl<BHK: BuildHashKernels> Filter<BHK> {
     pub fn new(items_count: usize, fp_rate: f64, build_hash_kernels: BHK) -> Self {
         let buckets = Buckets::with_fp_rate(items_count, fp_rate, 1);
         let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len());
-        Self { buckets, hash_kernels }
+        Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        }
     }
 
     pub fn with_raw_data(raw_data: &[u8], k: usize, build_hash_kernels: BHK) -> Self {
         let buckets = Buckets::with_raw_data(raw_data.len() * 8, 1, raw_data);
         let hash_kernels = build_hash_kernels.with_k(k, buckets.len());
-        Self { buckets, hash_kernels }
+        Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        }
     }
 
     pub fn buckets(&self) -> &Buckets {
         &self.buckets
     }
+
+    /// Returns the number of inserts since the creation or the last reset,
+    /// as `Count` of Go BoomFilters does, repeated inserts are counted.
+    /// Only `write_go` and serde save it, filters read by `read_from` or
+    /// opened by `open_mmap` start from 0.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Filter<DefaultBuildHashKernels<DefaultBuildHasher>> {
+    /// Writes the filter in the binary format of [`format`](crate::format),
+    /// without the count of inserts.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), format::Error> {
+        let header = Header {
+            kind: CLASSIC,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: 1,
+            count: self.buckets.len(),
+            k: self.hash_kernels.k(),
+            hash_seed: self.hash_kernels.hash_seed(),
+            p: 0,
+        };
+        format::write(writer, &header, &self.buckets.raw_data())
+    }
+
+    /// Reads a filter written by `write_to`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, format::Error> {
+        let (header, data) = format::read(reader, CLASSIC)?;
+        if header.bucket_size != 1 {
+            return Err(format::Error::Inconsistent("classic bloom filter buckets must be of 1 bit"));
+        }
+        let buckets = Buckets::with_raw_data(header.count, header.bucket_size, &data);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Creates a filter on a memory-mapped file, which is truncated if it
+    /// exists. The filter is mutated in place, see `flush`.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    #[cfg(feature = "mmap")]
+    pub fn create_mmap<P: AsRef<Path>>(
+        path: P,
+        items_count: usize,
+        fp_rate: f64,
+        hash_seed: usize,
+    ) -> Result<Self, format::Error> {
+        let count = compute_m_num(items_count, fp_rate);
+        let hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher).with_fp_rate(fp_rate, count);
+        let header = Header {
+            kind: CLASSIC,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: 1,
+            count,
+            k: hash_kernels.k(),
+            hash_seed,
+            p: 0,
+        };
+        let mmap = format::create_mmap(path.as_ref(), &header)?;
+        let buckets = Buckets::with_mmap(count, header.bucket_size, mmap);
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Opens a filter created by `create_mmap`, the count of inserts isn't
+    /// stored in the file and starts from 0.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self, format::Error> {
+        let (header, mmap) = format::open_mmap(path.as_ref(), CLASSIC)?;
+        if header.bucket_size != 1 {
+            return Err(format::Error::Inconsistent("classic bloom filter buckets must be of 1 bit"));
+        }
+        let buckets = Buckets::with_mmap(header.count, header.bucket_size, mmap);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Writes the modified buckets to the file of a filter created by
+    /// `create_mmap` or `open_mmap`, does nothing for other filters.
+    #[cfg(feature = "mmap")]
+    pub fn flush(&self) -> Result<(), format::Error> {
+        self.buckets.flush()?;
+        Ok(())
+    }
+}
+
+impl Filter<DefaultBuildHashKernels<BuildFnv1Hasher>> {
+    /// Writes the filter in the format of `BloomFilter.WriteTo` of Go
+    /// BoomFilters.
+    pub fn write_go<W: Write>(&self, mut writer: W) -> Result<(), format::Error> {
+        if self.hash_kernels.hash_seed() != 0 {
+            return Err(format::Error::Inconsistent("hash seed must be 0"));
+        }
+        go::write_u64(&mut writer, self.count)?;
+        go::write_u64(&mut writer, self.buckets.len())?;
+        go::write_u64(&mut writer, self.hash_kernels.k())?;
+        go::write_buckets(&mut writer, &self.buckets)
+    }
+
+    /// Reads a filter written by `BloomFilter.WriteTo` of Go BoomFilters.
+    pub fn read_go<R: Read>(mut reader: R) -> Result<Self, format::Error> {
+        let count = go::read_u64(&mut reader)?;
+        let m = go::read_u64(&mut reader)?;
+        let k = go::read_u64(&mut reader)?;
+        let buckets = go::read_buckets(&mut reader)?;
+        if buckets.bucket_size() != 1 || buckets.len() != m || k == 0 {
+            return Err(format::Error::Inconsistent("classic bloom filter m, k or buckets mismatch"));
+        }
+        let hash_kernels = DefaultBuildHashKernels::new(0, BuildFnv1Hasher).with_k(k, m);
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count,
+        })
+    }
 }
 
 impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
     fn insert<T: Hash>(&mut self, item: &T) {
-        self.hash_kernels.hash_iter(item).for_each(|i| self.buckets.set(i, 1))
+        self.hash_kernels.hash_iter(item).for_each(|i| self.buckets.set(i, 1));
+        self.count += 1;
     }
 
     fn contains<T: Hash>(&self, item: &T) -> bool {
@@ -38,7 +191,8 @@ impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
     }
 
     fn reset(&mut self) {
-        self.buckets.reset()
+        self.buckets.reset();
+        self.count = 0;
     }
 }
 
@@ -48,6 +202,72 @@ impl<BHK: BuildHashKernels> UpdatableBloomFilter for Filter<BHK> {
     }
 }
 
+/// Read-only view of a classic bloom filter over borrowed `raw_data`, for
+/// instance a memory-mapped file or a network buffer, without copying it.
+pub struct FilterRef<'a, BHK: BuildHashKernels> {
+    buckets: BucketsRef<'a>, // filter data
+    hash_kernels: BHK::HK,   // hash kernels
+}
+
+impl<'a, BHK: BuildHashKernels> FilterRef<'a, BHK> {
+    pub fn with_raw_data(raw_data: &'a [u8], k: usize, build_hash_kernels: BHK) -> Self {
+        let buckets = BucketsRef::with_raw_data(raw_data.len() * 8, 1, raw_data);
+        let hash_kernels = build_hash_kernels.with_k(k, buckets.len());
+        Self { buckets, hash_kernels }
+    }
+
+    pub fn buckets(&self) -> &BucketsRef<'a> {
+        &self.buckets
+    }
+
+    pub fn contains<T: Hash>(&self, item: &T) -> bool {
+        self.hash_kernels.hash_iter(item).all(|i| self.buckets.get(i) == 1)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<BH: BuildHasher + Serialize> Serialize for Filter<DefaultBuildHashKernels<BH>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ClassicBloomFilter", 3)?;
+        state.serialize_field("buckets", &self.buckets)?;
+        state.serialize_field("hash_kernels", &self.hash_kernels)?;
+        state.serialize_field("count", &self.count)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, BH: BuildHasher + Deserialize<'de>> Deserialize<'de> for Filter<DefaultBuildHashKernels<BH>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "ClassicBloomFilter")]
+        struct Data<BH> {
+            buckets: Buckets,
+            hash_kernels: DefaultHashKernels<BH>,
+            // missing in the data of older releases
+            #[serde(default)]
+            count: usize,
+        }
+
+        let Data {
+            buckets,
+            hash_kernels,
+            count,
+        } = Data::deserialize(deserializer)?;
+        if buckets.max_value() != 1 {
+            return Err(D::Error::custom("classic bloom filter buckets must be of 1 bit"));
+        }
+        if hash_kernels.len() != buckets.len() {
+            return Err(D::Error::custom("hash kernels size doesn't match the buckets count"));
+        }
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,6 +308,27 @@ mod tests {
         }
     }
 
+    fn _raw_data_ref(items: &[usize]) {
+        let hash_seed = random();
+        let build_hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher);
+        let mut filter = Filter::with_raw_data(&[0; 16], 3, build_hash_kernels);
+        items.iter().for_each(|i| filter.insert(i));
+        // an unaligned copy of the raw data
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&filter.buckets().raw_data());
+        let build_hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher);
+        let filter_ref = FilterRef::with_raw_data(&bytes[1..], 3, build_hash_kernels);
+        assert!(items.iter().all(|i| filter_ref.contains(i)));
+        assert!((0..1000).all(|i| filter.contains(&i) == filter_ref.contains(&i)));
+    }
+
+    proptest! {
+        #[test]
+        fn raw_data_ref(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _raw_data_ref(items)
+        }
+    }
+
     fn _update(items1: &[usize], items2: &[usize]) {
         let data = vec![0; 8];
         let hash_seed = random();
@@ -112,4 +353,134 @@ mod tests {
             _update(items1, items2)
         }
     }
+
+    fn _write_to(items: &[usize]) {
+        let mut filter = Filter::new(100, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        let filter = Filter::read_from(&bytes[..]).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+        // the count of inserts isn't stored
+        assert_eq!(0, filter.count());
+        assert!(matches!(
+            crate::CountingBloomFilter::read_from(&bytes[..]),
+            Err(format::Error::Kind(format::CLASSIC))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn write_to(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _write_to(items)
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    fn _mmap(items: &[usize]) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("classic");
+        let mut filter = Filter::create_mmap(&path, 100, 0.03, random()).unwrap();
+        items.iter().for_each(|i| filter.insert(i));
+        filter.flush().unwrap();
+        drop(filter);
+
+        let mut filter = Filter::open_mmap(&path).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+        filter.reset();
+        drop(filter);
+        assert!(items.iter().all(|i| !Filter::open_mmap(&path).unwrap().contains(i)));
+        assert!(matches!(
+            crate::CountingBloomFilter::open_mmap(&path),
+            Err(format::Error::Kind(format::CLASSIC))
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    proptest! {
+        #[test]
+        fn mmap(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _mmap(items)
+        }
+    }
+
+    // written as BoomFilters writes "alpha" and "beta", built from the Go source by
+    // hand until replaced by the output of testdata/boomfilters/main.go
+    const GO_CLASSIC: &str = "000000000000000200000000000000400000000000000003\
+                              010100000000000000400000000000000008000000082100a800";
+
+    #[test]
+    fn go() {
+        use crate::go::GoBytes;
+
+        let bytes = (0..GO_CLASSIC.len() / 2)
+            .map(|i| u8::from_str_radix(&GO_CLASSIC[i * 2..i * 2 + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        let mut filter = Filter::read_go(&bytes[..]).unwrap();
+        assert!(filter.contains(&GoBytes(b"alpha")));
+        assert!(filter.contains(&GoBytes(b"beta")));
+        assert!(!filter.contains(&GoBytes(b"gamma")));
+
+        filter.reset();
+        filter.insert(&GoBytes(b"alpha"));
+        filter.insert(&GoBytes(b"beta"));
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(bytes, written);
+    }
+
+    #[test]
+    fn go_count() {
+        use crate::go::GoBytes;
+
+        let mut bytes = (0..GO_CLASSIC.len() / 2)
+            .map(|i| u8::from_str_radix(&GO_CLASSIC[i * 2..i * 2 + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        // any count written by Go is kept, repeated inserts are counted
+        bytes[7] = 7;
+        let mut filter = Filter::read_go(&bytes[..]).unwrap();
+        assert_eq!(7, filter.count());
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(bytes, written);
+
+        filter.insert(&GoBytes(b"alpha"));
+        assert_eq!(8, filter.count());
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(8, written[7]);
+        filter.reset();
+        assert_eq!(0, filter.count());
+    }
+
+    #[cfg(feature = "serde")]
+    fn _serde(items: &[usize]) {
+        let mut filter = Filter::new(100, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let json = serde_json::to_string(&filter).unwrap();
+        let filter: Filter<DefaultBuildHashKernels<DefaultBuildHasher>> = serde_json::from_str(&json).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    #[cfg(feature = "serde")]
+    proptest! {
+        #[test]
+        fn serde(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _serde(items)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_inconsistent() {
+        let buckets = r#"{"count":64,"bucket_size":1,"data":[0]}"#;
+        let kernels = |k, n| format!(r#"{{"k":{},"n":{},"hash_seed":0,"build_hasher":null}}"#, k, n);
+        let json = |k, n| format!(r#"{{"buckets":{},"hash_kernels":{}}}"#, buckets, kernels(k, n));
+        type F = Filter<DefaultBuildHashKernels<DefaultBuildHasher>>;
+        assert!(serde_json::from_str::<F>(&json(3, 64)).is_ok());
+        assert!(serde_json::from_str::<F>(&json(3, 63)).is_err());
+        assert!(serde_json::from_str::<F>(&json(0, 64)).is_err());
+        let json = r#"{"buckets":{"count":32,"bucket_size":2,"data":[0]},"hash_kernels":{"k":3,"n":32,"hash_seed":0,"build_hasher":null}}"#;
+        assert!(serde_json::from_str::<F>(json).is_err());
+    }
 }
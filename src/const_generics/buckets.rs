@@ -1,3 +1,5 @@
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use std::f64::consts::LN_2;
 use std::mem::size_of;
 use std::ptr::copy_nonoverlapping;
@@ -84,6 +86,11 @@ impl<const W: usize> ConstBuckets<W> {
         self.max
     }
 
+    #[inline(always)]
+    pub fn bucket_size(&self) -> u8 {
+        self.bucket_size
+    }
+
     pub fn reset(&mut self) {
         self.data.iter_mut().for_each(|x| *x = 0)
     }
@@ -139,6 +146,55 @@ impl<const W: usize> ConstBuckets<W> {
     }
 }
 
+// serialized in the same format as `Buckets`
+#[cfg(feature = "serde")]
+impl<const W: usize> Serialize for ConstBuckets<W> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Buckets", 3)?;
+        state.serialize_field("count", &self.bucket_count)?;
+        state.serialize_field("bucket_size", &self.bucket_size)?;
+        state.serialize_field("data", &self.data[..])?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const W: usize> Deserialize<'de> for ConstBuckets<W> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Buckets")]
+        struct Data {
+            count: usize,
+            bucket_size: u8,
+            data: Vec<Word>,
+        }
+
+        let Data {
+            count,
+            bucket_size,
+            data: words,
+        } = Data::deserialize(deserializer)?;
+        if bucket_size == 0 || bucket_size >= 8 {
+            return Err(D::Error::custom("bucket size must be in [1, 7]"));
+        }
+        if words.len() != W {
+            return Err(D::Error::custom("buckets data length doesn't match the words count"));
+        }
+        let bits = count.checked_mul(bucket_size as usize);
+        if bits.filter(|bits| *bits <= W * BITS_PER_WORD).is_none() {
+            return Err(D::Error::custom("buckets count doesn't fit in the words count"));
+        }
+        let mut data = [0; W];
+        data.copy_from_slice(&words);
+        Ok(Self {
+            data,
+            bucket_count: count,
+            bucket_size,
+            max: (1u8 << bucket_size) - 1,
+        })
+    }
+}
+
 pub const fn compute_word_num(bucket_count: usize, bucket_size: u8) -> usize {
     (bucket_count * bucket_size as usize + BITS_PER_WORD - 1) / BITS_PER_WORD
 }
@@ -280,4 +336,21 @@ mod tests {
         assert_eq!(1, b1.get(49));
         assert_eq!(1, b1.get(63));
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let mut buckets = ConstBuckets::<{ compute_word_num(100, 3) }>::new(100, 3);
+        buckets.set(0, 1);
+        buckets.set(21, 6);
+        let json = serde_json::to_string(&buckets).unwrap();
+        let buckets: ConstBuckets<{ compute_word_num(100, 3) }> = serde_json::from_str(&json).unwrap();
+        assert_eq!(100, buckets.len());
+        assert_eq!(1, buckets.get(0));
+        assert_eq!(6, buckets.get(21));
+
+        assert!(serde_json::from_str::<ConstBuckets<{ compute_word_num(100, 1) }>>(&json).is_err());
+        let json = r#"{"count":200,"bucket_size":3,"data":[0,0,0,0,0]}"#;
+        assert!(serde_json::from_str::<ConstBuckets<{ compute_word_num(100, 3) }>>(json).is_err());
+    }
 }
@@ -1,6 +1,15 @@
-use crate::const_generics::buckets::ConstBuckets;
+use crate::const_generics::buckets::{compute_word_num, ConstBuckets};
+use crate::format::{self, Header, CLASSIC, DEFAULT_HASH_KERNELS};
+#[cfg(feature = "serde")]
+use crate::hash::DefaultHashKernels;
+use crate::hash::{DefaultBuildHashKernels, DefaultBuildHasher};
 use crate::{BloomFilter, BuildHashKernels, HashKernels};
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::io::{Read, Write};
 
 #[derive(Clone)]
 pub struct Filter<BHK: BuildHashKernels, const W: usize> {
@@ -29,6 +38,40 @@ impl<BHK: BuildHashKernels, const W: usize> Filter<BHK, W> {
     }
 }
 
+impl<const W: usize> Filter<DefaultBuildHashKernels<DefaultBuildHasher>, W> {
+    /// Writes the filter in the binary format of [`format`](crate::format).
+    pub fn write_to<T: Write>(&self, writer: T) -> Result<(), format::Error> {
+        let header = Header {
+            kind: CLASSIC,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: 1,
+            count: self.buckets.len(),
+            k: self.hash_kernels.k(),
+            hash_seed: self.hash_kernels.hash_seed(),
+            p: 0,
+        };
+        // only the words of the buckets, W may be larger
+        let len = compute_word_num(header.count, header.bucket_size) * 8;
+        format::write(writer, &header, &self.buckets.raw_data()[..len])
+    }
+
+    /// Reads a filter written by `write_to`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, format::Error> {
+        let (header, mut data) = format::read(reader, CLASSIC)?;
+        if header.bucket_size != 1 {
+            return Err(format::Error::Inconsistent("classic bloom filter buckets must be of 1 bit"));
+        }
+        if data.len() > W * 8 {
+            return Err(format::Error::Inconsistent("data length doesn't fit in the words count"));
+        }
+        data.resize(W * 8, 0);
+        let buckets = ConstBuckets::with_raw_data(header.count, header.bucket_size, &data);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self { buckets, hash_kernels })
+    }
+}
+
 impl<BHK: BuildHashKernels, const W: usize> BloomFilter for Filter<BHK, W> {
     fn insert<T: Hash>(&mut self, item: &T) {
         self.hash_kernels.hash_iter(item).for_each(|i| self.buckets.set(i, 1))
@@ -43,6 +86,37 @@ impl<BHK: BuildHashKernels, const W: usize> BloomFilter for Filter<BHK, W> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<BH: BuildHasher + Serialize, const W: usize> Serialize for Filter<DefaultBuildHashKernels<BH>, W> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ConstClassicBloomFilter", 2)?;
+        state.serialize_field("buckets", &self.buckets)?;
+        state.serialize_field("hash_kernels", &self.hash_kernels)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, BH: BuildHasher + Deserialize<'de>, const W: usize> Deserialize<'de> for Filter<DefaultBuildHashKernels<BH>, W> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "ConstClassicBloomFilter")]
+        struct Data<BH, const W: usize> {
+            buckets: ConstBuckets<W>,
+            hash_kernels: DefaultHashKernels<BH>,
+        }
+
+        let Data { buckets, hash_kernels } = Data::<BH, W>::deserialize(deserializer)?;
+        if buckets.max_value() != 1 {
+            return Err(D::Error::custom("classic bloom filter buckets must be of 1 bit"));
+        }
+        if hash_kernels.len() != buckets.len() {
+            return Err(D::Error::custom("hash kernels size doesn't match the buckets count"));
+        }
+        Ok(Self { buckets, hash_kernels })
+    }
+}
+
 // Calculates the buckets count approximately(bigger than how many system needs)
 #[macro_export]
 macro_rules! classicfilter {
@@ -85,4 +159,47 @@ mod tests {
             _contains(items)
         }
     }
+
+    fn _write_to(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        const W: usize = compute_word_num(approximate_bucket_count(100), 1);
+        let mut filter = Filter::<_, W>::new(100, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        let filter = Filter::<_, W>::read_from(&bytes[..]).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+        assert!(matches!(
+            Filter::<_, 1>::read_from(&bytes[..]),
+            Err(format::Error::Inconsistent(_))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn write_to(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _write_to(items)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn _serde(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        const W: usize = compute_word_num(approximate_bucket_count(100), 1);
+        let mut filter = Filter::<_, W>::new(100, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let json = serde_json::to_string(&filter).unwrap();
+        let filter: Filter<DefaultBuildHashKernels<DefaultBuildHasher>, W> = serde_json::from_str(&json).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    #[cfg(feature = "serde")]
+    proptest! {
+        #[test]
+        fn serde(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _serde(items)
+        }
+    }
 }
@@ -0,0 +1,210 @@
+use crate::hash::DefaultHashKernels;
+use crate::{BuildHashKernels, DefaultBuildHashKernels, HashKernels};
+use std::hash::{BuildHasher, Hash};
+
+/// Count Sketch with signed counters, each row adds `sign * count` to one
+/// bucket, the estimation is the median of `sign * bucket` over all rows and
+/// is unbiased.
+pub struct Sketch<BH: BuildHasher> {
+    counters: Vec<i64>,                     // depth rows of width counters
+    width: usize,                           // counters per row
+    depth: usize,                           // number of rows
+    hash_seed: usize,                       // seed offset for anonymity and privacy purpose
+    bucket_kernels: DefaultHashKernels<BH>, // one bucket per row
+    sign_kernels: DefaultHashKernels<BH>,   // one ±1 sign per row
+}
+
+impl<BH: BuildHasher + Clone> Sketch<BH> {
+    /// Creates a new Count Sketch with depth rows of width counters.
+    pub fn new(width: usize, depth: usize, hash_seed: usize, build_hasher: BH) -> Self {
+        debug_assert!(width > 0 && depth > 0);
+        let bucket_kernels = DefaultBuildHashKernels::new(hash_seed, build_hasher.clone()).with_k(depth, width);
+        let sign_kernels = DefaultBuildHashKernels::new(hash_seed, build_hasher).with_k(1, 2);
+        Self {
+            counters: vec![0; width * depth],
+            width,
+            depth,
+            hash_seed,
+            bucket_kernels,
+            sign_kernels,
+        }
+    }
+
+    /// Creates a new Count Sketch whose estimations are within epsilon * L2 norm
+    /// of the true frequencies with probability 1 - delta.
+    pub fn with_error(epsilon: f64, delta: f64, hash_seed: usize, build_hasher: BH) -> Self {
+        debug_assert!(epsilon > 0.0 && epsilon < 1.0);
+        debug_assert!(delta > 0.0 && delta < 1.0);
+        let width = (3.0 / (epsilon * epsilon)).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        Self::new(width, depth.max(1), hash_seed, build_hasher)
+    }
+}
+
+impl<BH: BuildHasher> Sketch<BH> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn add<T: Hash>(&mut self, item: &T, count: i64) {
+        for (row, bucket) in self.bucket_kernels.hash_iter(item).enumerate() {
+            let delta = self.sign(row, item) * count;
+            let counter = &mut self.counters[row * self.width + bucket];
+            *counter = counter.saturating_add(delta);
+        }
+    }
+
+    pub fn subtract<T: Hash>(&mut self, item: &T, count: i64) {
+        self.add(item, -count)
+    }
+
+    pub fn estimate<T: Hash>(&self, item: &T) -> i64 {
+        let estimates = self
+            .bucket_kernels
+            .hash_iter(item)
+            .enumerate()
+            .map(|(row, bucket)| self.sign(row, item) * self.counters[row * self.width + bucket])
+            .collect();
+        median(estimates)
+    }
+
+    /// Merges another sketch created with the same dimensions, hash seed and hasher.
+    pub fn merge(&mut self, other: &Self) {
+        assert!(
+            self.is_compatible(other),
+            "count sketches must have the same dimensions and hash seed"
+        );
+        self.counters
+            .iter_mut()
+            .zip(other.counters.iter())
+            .for_each(|(a, b)| *a = a.saturating_add(*b));
+    }
+
+    /// Estimates the inner product of the frequency vectors of two sketches
+    /// created with the same dimensions, hash seed and hasher.
+    pub fn inner_product(&self, other: &Self) -> i64 {
+        assert!(
+            self.is_compatible(other),
+            "count sketches must have the same dimensions and hash seed"
+        );
+        let estimates = self
+            .counters
+            .chunks(self.width)
+            .zip(other.counters.chunks(other.width))
+            .map(|(a, b)| {
+                a.iter()
+                    .zip(b)
+                    .fold(0i64, |acc, (a, b)| acc.saturating_add(a.saturating_mul(*b)))
+            })
+            .collect();
+        median(estimates)
+    }
+
+    /// Estimates the L2 norm of the frequency vector.
+    pub fn l2_norm(&self) -> f64 {
+        (self.inner_product(self).max(0) as f64).sqrt()
+    }
+
+    /// Returns the candidates whose estimated frequency is at least phi * L2 norm.
+    pub fn heavy_hitters<'a, T: Hash>(&self, candidates: &'a [T], phi: f64) -> Vec<&'a T> {
+        let threshold = phi * self.l2_norm();
+        candidates
+            .iter()
+            .filter(|item| self.estimate(item).abs() as f64 >= threshold)
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.counters.iter_mut().for_each(|x| *x = 0)
+    }
+
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.width == other.width && self.depth == other.depth && self.hash_seed == other.hash_seed
+    }
+
+    fn sign<T: Hash>(&self, row: usize, item: &T) -> i64 {
+        match self.sign_kernels.hash_iter(&(row, item)).next() {
+            Some(0) => -1,
+            _ => 1,
+        }
+    }
+}
+
+// returns the median, the mean of the two middle values for even lengths
+fn median(mut values: Vec<i64>) -> i64 {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() & 1 == 1 {
+        values[mid]
+    } else {
+        ((values[mid - 1] as i128 + values[mid] as i128) / 2) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHasher;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::HashMap;
+
+    fn _estimate(items: &[u8]) {
+        let mut sketch = Sketch::new(4096, 5, random(), DefaultBuildHasher);
+        let mut counts = HashMap::new();
+        items.iter().for_each(|i| {
+            sketch.add(i, 1);
+            *counts.entry(i).or_insert(0) += 1;
+        });
+        assert!(counts.iter().all(|(i, count)| sketch.estimate(i) == *count));
+        items.iter().for_each(|i| sketch.subtract(i, 1));
+        assert!(counts.keys().all(|i| sketch.estimate(i) == 0));
+    }
+
+    proptest! {
+        #[test]
+        fn estimate(ref items in any_with::<Vec<u8>>(size_range(16).lift())) {
+            _estimate(items)
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let hash_seed = random();
+        let mut a = Sketch::new(1024, 5, hash_seed, DefaultBuildHasher);
+        let mut b = Sketch::new(1024, 5, hash_seed, DefaultBuildHasher);
+        a.add(&"foo", 3);
+        b.add(&"foo", 4);
+        b.add(&"bar", 5);
+        a.merge(&b);
+        assert_eq!(7, a.estimate(&"foo"));
+        assert_eq!(5, a.estimate(&"bar"));
+    }
+
+    #[test]
+    fn inner_product() {
+        let hash_seed = random();
+        let mut a = Sketch::new(1024, 5, hash_seed, DefaultBuildHasher);
+        let mut b = Sketch::new(1024, 5, hash_seed, DefaultBuildHasher);
+        a.add(&1, 3);
+        a.add(&2, 4);
+        b.add(&2, 5);
+        b.add(&3, 6);
+        assert_eq!(20, a.inner_product(&b));
+        assert_eq!(25, a.inner_product(&a));
+        assert!((a.l2_norm() - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn heavy_hitters() {
+        let mut sketch = Sketch::with_error(0.05, 0.01, random(), DefaultBuildHasher);
+        (0..1000u32).for_each(|i| sketch.add(&i, 1));
+        sketch.add(&7u32, 1000);
+        let candidates = (0..1000u32).collect::<Vec<_>>();
+        assert_eq!(vec![&7], sketch.heavy_hitters(&candidates, 0.5));
+    }
+}
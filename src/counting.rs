@@ -1,10 +1,25 @@
+#[cfg(feature = "mmap")]
+use crate::buckets::compute_m_num;
 use crate::buckets::Buckets;
+use crate::format::{self, Header, COUNTING, DEFAULT_HASH_KERNELS};
+use crate::go::{self, BuildFnv1Hasher};
+#[cfg(feature = "serde")]
+use crate::hash::DefaultHashKernels;
+use crate::hash::{DefaultBuildHashKernels, DefaultBuildHasher};
 use crate::{BloomFilter, BuildHashKernels, HashKernels, RemovableBloomFilter};
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::io::{Read, Write};
+#[cfg(feature = "mmap")]
+use std::path::Path;
 
 pub struct Filter<BHK: BuildHashKernels> {
     buckets: Buckets,      // filter data
     hash_kernels: BHK::HK, // hash kernels
+    count: usize,          // number of inserts less the removes, the count of Go BoomFilters
 }
 
 impl<BHK: BuildHashKernels> Filter<BHK> {
@@ -15,13 +30,155 @@ impl<BHK: BuildHashKernels> Filter<BHK> {
     pub fn new(items_count: usize, bucket_size: u8, fp_rate: f64, build_hash_kernels: BHK) -> Self {
         let buckets = Buckets::with_fp_rate(items_count, fp_rate, bucket_size);
         let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len());
-        Self { buckets, hash_kernels }
+        Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        }
+    }
+
+    /// Returns the number of inserts less the items removed by
+    /// `test_and_remove` since the creation or the last reset, as `Count` of
+    /// Go BoomFilters does. `remove` doesn't change it. The binary format and
+    /// the memory-mapped files don't store it, so it starts from 0 after
+    /// `read_from` and `open_mmap`.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Removes an item if it may be in the filter and returns whether it was,
+    /// as `TestAndRemove` of Go BoomFilters does.
+    pub fn test_and_remove<T: Hash>(&mut self, item: &T) -> bool {
+        let member = self.contains(item);
+        if member {
+            self.remove(item);
+            self.count = self.count.saturating_sub(1);
+        }
+        member
+    }
+}
+
+impl Filter<DefaultBuildHashKernels<DefaultBuildHasher>> {
+    /// Writes the filter in the binary format of [`format`](crate::format),
+    /// without the count of inserts.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), format::Error> {
+        let header = Header {
+            kind: COUNTING,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: self.buckets.bucket_size(),
+            count: self.buckets.len(),
+            k: self.hash_kernels.k(),
+            hash_seed: self.hash_kernels.hash_seed(),
+            p: 0,
+        };
+        format::write(writer, &header, &self.buckets.raw_data())
+    }
+
+    /// Reads a filter written by `write_to`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, format::Error> {
+        let (header, data) = format::read(reader, COUNTING)?;
+        let buckets = Buckets::with_raw_data(header.count, header.bucket_size, &data);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Creates a filter on a memory-mapped file, which is truncated if it
+    /// exists. The filter is mutated in place, see `flush`.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// bucket_size is the specified number of bits
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    #[cfg(feature = "mmap")]
+    pub fn create_mmap<P: AsRef<Path>>(
+        path: P,
+        items_count: usize,
+        bucket_size: u8,
+        fp_rate: f64,
+        hash_seed: usize,
+    ) -> Result<Self, format::Error> {
+        let count = compute_m_num(items_count, fp_rate);
+        let hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher).with_fp_rate(fp_rate, count);
+        let header = Header {
+            kind: COUNTING,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size,
+            count,
+            k: hash_kernels.k(),
+            hash_seed,
+            p: 0,
+        };
+        let mmap = format::create_mmap(path.as_ref(), &header)?;
+        let buckets = Buckets::with_mmap(count, header.bucket_size, mmap);
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Opens a filter created by `create_mmap`, the count of inserts isn't
+    /// stored in the file and starts from 0.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self, format::Error> {
+        let (header, mmap) = format::open_mmap(path.as_ref(), COUNTING)?;
+        let buckets = Buckets::with_mmap(header.count, header.bucket_size, mmap);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count: 0,
+        })
+    }
+
+    /// Writes the modified buckets to the file of a filter created by
+    /// `create_mmap` or `open_mmap`, does nothing for other filters.
+    #[cfg(feature = "mmap")]
+    pub fn flush(&self) -> Result<(), format::Error> {
+        self.buckets.flush()?;
+        Ok(())
+    }
+}
+
+impl Filter<DefaultBuildHashKernels<BuildFnv1Hasher>> {
+    /// Writes the filter in the format of `CountingBloomFilter.WriteTo` of Go
+    /// BoomFilters.
+    pub fn write_go<W: Write>(&self, mut writer: W) -> Result<(), format::Error> {
+        if self.hash_kernels.hash_seed() != 0 {
+            return Err(format::Error::Inconsistent("hash seed must be 0"));
+        }
+        go::write_u64(&mut writer, self.count)?;
+        go::write_u64(&mut writer, self.buckets.len())?;
+        go::write_u64(&mut writer, self.hash_kernels.k())?;
+        go::write_buckets(&mut writer, &self.buckets)
+    }
+
+    /// Reads a filter written by `CountingBloomFilter.WriteTo` of Go BoomFilters.
+    pub fn read_go<R: Read>(mut reader: R) -> Result<Self, format::Error> {
+        let count = go::read_u64(&mut reader)?;
+        let m = go::read_u64(&mut reader)?;
+        let k = go::read_u64(&mut reader)?;
+        let buckets = go::read_buckets(&mut reader)?;
+        if buckets.len() != m || k == 0 {
+            return Err(format::Error::Inconsistent("counting bloom filter m, k or buckets mismatch"));
+        }
+        let hash_kernels = DefaultBuildHashKernels::new(0, BuildFnv1Hasher).with_k(k, m);
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count,
+        })
     }
 }
 
 impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
     fn insert<T: Hash>(&mut self, item: &T) {
-        self.hash_kernels.hash_iter(item).for_each(|i| self.buckets.increment(i, 1))
+        self.hash_kernels.hash_iter(item).for_each(|i| self.buckets.increment(i, 1));
+        self.count += 1;
     }
 
     fn contains<T: Hash>(&self, item: &T) -> bool {
@@ -29,7 +186,8 @@ impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
     }
 
     fn reset(&mut self) {
-        self.buckets.reset()
+        self.buckets.reset();
+        self.count = 0;
     }
 }
 
@@ -39,6 +197,46 @@ impl<BHK: BuildHashKernels> RemovableBloomFilter for Filter<BHK> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<BH: BuildHasher + Serialize> Serialize for Filter<DefaultBuildHashKernels<BH>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CountingBloomFilter", 3)?;
+        state.serialize_field("buckets", &self.buckets)?;
+        state.serialize_field("hash_kernels", &self.hash_kernels)?;
+        state.serialize_field("count", &self.count)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, BH: BuildHasher + Deserialize<'de>> Deserialize<'de> for Filter<DefaultBuildHashKernels<BH>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "CountingBloomFilter")]
+        struct Data<BH> {
+            buckets: Buckets,
+            hash_kernels: DefaultHashKernels<BH>,
+            // absent from filters serialized without a count
+            #[serde(default)]
+            count: usize,
+        }
+
+        let Data {
+            buckets,
+            hash_kernels,
+            count,
+        } = Data::deserialize(deserializer)?;
+        if hash_kernels.len() != buckets.len() {
+            return Err(D::Error::custom("hash kernels size doesn't match the buckets count"));
+        }
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            count,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,4 +272,131 @@ mod tests {
             _remove(items)
         }
     }
+
+    fn _write_to(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        let mut filter = Filter::new(100, 4, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        let mut filter = Filter::read_from(&bytes[..]).unwrap();
+        assert_eq!(4, filter.buckets.bucket_size());
+        // the count of inserts isn't stored
+        assert_eq!(0, filter.count());
+        assert!(items.iter().all(|i| filter.contains(i)));
+        items.iter().for_each(|i| filter.remove(i));
+        assert!(items.iter().all(|i| !filter.contains(i)));
+
+        let len = bytes.len();
+        bytes[len - 5] ^= 1;
+        assert!(matches!(Filter::read_from(&bytes[..]), Err(format::Error::Checksum)));
+    }
+
+    proptest! {
+        #[test]
+        fn write_to(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _write_to(items)
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    fn _mmap(items: &[usize]) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("counting");
+        let mut filter = Filter::create_mmap(&path, 100, 4, 0.03, random()).unwrap();
+        items.iter().for_each(|i| filter.insert(i));
+        filter.flush().unwrap();
+        drop(filter);
+
+        let mut filter = Filter::open_mmap(&path).unwrap();
+        assert_eq!(4, filter.buckets.bucket_size());
+        assert!(items.iter().all(|i| filter.contains(i)));
+        items.iter().for_each(|i| filter.remove(i));
+        filter.flush().unwrap();
+        drop(filter);
+        assert!(items.iter().all(|i| !Filter::open_mmap(&path).unwrap().contains(i)));
+    }
+
+    #[cfg(feature = "mmap")]
+    proptest! {
+        #[test]
+        fn mmap(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _mmap(items)
+        }
+    }
+
+    // written as BoomFilters writes "alpha" and "beta", built from the Go source by
+    // hand until replaced by the output of testdata/boomfilters/main.go
+    const GO_COUNTING: &str = "000000000000000200000000000000100000000000000003\
+                               040f000000000000001000000000000000080110201000100000";
+
+    #[test]
+    fn go() {
+        use crate::go::GoBytes;
+
+        let bytes = (0..GO_COUNTING.len() / 2)
+            .map(|i| u8::from_str_radix(&GO_COUNTING[i * 2..i * 2 + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        let mut filter = Filter::read_go(&bytes[..]).unwrap();
+        assert!(filter.contains(&GoBytes(b"alpha")));
+        assert!(filter.contains(&GoBytes(b"beta")));
+
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(bytes, written);
+
+        assert!(filter.test_and_remove(&GoBytes(b"alpha")));
+        assert!(!filter.contains(&GoBytes(b"alpha")));
+        assert!(filter.contains(&GoBytes(b"beta")));
+        assert_eq!(1, filter.count());
+    }
+
+    #[test]
+    fn go_count() {
+        use crate::go::GoBytes;
+
+        // the counters of 1 bit saturate, the count doesn't
+        let mut filter = Filter::new(100, 1, 0.03, DefaultBuildHashKernels::new(0, BuildFnv1Hasher));
+        (0..3).for_each(|_| filter.insert(&GoBytes(b"alpha")));
+        assert_eq!(3, filter.count());
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(3, written[7]);
+        let mut filter = Filter::read_go(&written[..]).unwrap();
+        assert_eq!(3, filter.count());
+
+        // removing an item which isn't in the filter changes nothing
+        assert!(!filter.test_and_remove(&GoBytes(b"gamma")));
+        assert_eq!(3, filter.count());
+        assert!(filter.test_and_remove(&GoBytes(b"alpha")));
+        assert_eq!(2, filter.count());
+        assert!(!filter.contains(&GoBytes(b"alpha")));
+        // remove decrements the counters of any item and keeps the count
+        filter.remove(&GoBytes(b"gamma"));
+        assert_eq!(2, filter.count());
+        filter.reset();
+        assert_eq!(0, filter.count());
+    }
+
+    #[cfg(feature = "serde")]
+    fn _serde(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        let mut filter = Filter::new(100, 4, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let json = serde_json::to_string(&filter).unwrap();
+        let mut filter: Filter<DefaultBuildHashKernels<DefaultBuildHasher>> = serde_json::from_str(&json).unwrap();
+        assert!(items.iter().all(|i| filter.contains(i)));
+        items.iter().for_each(|i| filter.remove(i));
+        assert!(items.iter().all(|i| !filter.contains(i)));
+    }
+
+    #[cfg(feature = "serde")]
+    proptest! {
+        #[test]
+        fn serde(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _serde(items)
+        }
+    }
 }
@@ -0,0 +1,363 @@
+//! Self-describing binary format of filters.
+//!
+//! All integers are little-endian:
+//!
+//! | field        | size | description                                        |
+//! |--------------|------|----------------------------------------------------|
+//! | magic        | 4    | `b"BLMF"`                                          |
+//! | version      | 1    | format version, currently 1                        |
+//! | kind         | 1    | 1: classic, 2: counting, 3: stable                 |
+//! | hash kernels | 1    | 1: `DefaultHashKernels` with `DefaultBuildHasher`  |
+//! | bucket size  | 1    | bits of a bucket, in [1, 7]                        |
+//! | count        | 8    | number of buckets                                  |
+//! | k            | 8    | number of hash functions                           |
+//! | hash seed    | 8    | seed of the hash kernels                           |
+//! | p            | 8    | buckets decremented per insert, 0 if not stable    |
+//! | data length  | 8    | length of data in bytes                            |
+//! | data         | ...  | `raw_data` of the buckets                          |
+//! | crc          | 4    | CRC-32 (IEEE) of all the previous bytes            |
+//!
+//! With the `mmap` feature, classic and counting filters can live in a
+//! memory-mapped file: the same header with the magic `b"BLMM"`, followed by
+//! the data and no crc, as the buckets are mutated in place. The words of the
+//! data are in the native byte order, so only little-endian targets support it.
+//!
+//! `DefaultBuildHasher` uses the std `DefaultHasher`, whose algorithm may
+//! change between Rust releases, so files should be read by binaries built
+//! with the same Rust release as the writer.
+
+#[cfg(feature = "mmap")]
+use memmap2::MmapMut;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+#[cfg(feature = "mmap")]
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"BLMF";
+#[cfg(feature = "mmap")]
+const MMAP_MAGIC: [u8; 4] = *b"BLMM";
+const VERSION: u8 = 1;
+pub(crate) const HEADER_LEN: usize = 48;
+
+pub(crate) const CLASSIC: u8 = 1;
+pub(crate) const COUNTING: u8 = 2;
+pub(crate) const STABLE: u8 = 3;
+
+// `DefaultHashKernels` with `DefaultBuildHasher`
+pub(crate) const DEFAULT_HASH_KERNELS: u8 = 1;
+
+/// Errors of reading and writing filters.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The data doesn't start with the magic bytes.
+    Magic,
+    /// The format version isn't supported.
+    Version(u8),
+    /// The data is of another kind of filter.
+    Kind(u8),
+    /// The hash kernels aren't supported.
+    HashKernels(u8),
+    /// The parameters don't match each other or the filter type.
+    Inconsistent(&'static str),
+    /// The checksum doesn't match, the data is corrupted.
+    Checksum,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Magic => write!(f, "invalid magic bytes"),
+            Error::Version(version) => write!(f, "unsupported format version {}", version),
+            Error::Kind(kind) => write!(f, "unexpected filter kind {}", kind),
+            Error::HashKernels(id) => write!(f, "unsupported hash kernels {}", id),
+            Error::Inconsistent(reason) => write!(f, "inconsistent parameters: {}", reason),
+            Error::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Parameters of a filter.
+pub(crate) struct Header {
+    pub kind: u8,
+    pub hash_kernels: u8,
+    pub bucket_size: u8,
+    pub count: usize,
+    pub k: usize,
+    pub hash_seed: usize,
+    pub p: usize,
+}
+
+pub(crate) fn write<W: Write>(mut writer: W, header: &Header, data: &[u8]) -> Result<(), Error> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + data.len() + 4);
+    bytes.extend_from_slice(&encode_header(MAGIC, header, data.len()));
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Reads a filter of `kind`, returns its parameters and the `raw_data` of its
+/// buckets, which length is checked against the bucket count and size.
+pub(crate) fn read<R: Read>(mut reader: R, kind: u8) -> Result<(Header, Vec<u8>), Error> {
+    let mut bytes = vec![0; HEADER_LEN];
+    reader.read_exact(&mut bytes)?;
+    let (header, len) = decode_header(&bytes, MAGIC, kind)?;
+    // the length isn't trusted before the crc is checked, so the data is
+    // read as it comes instead of allocated upfront
+    let mut data = Vec::new();
+    (&mut reader).take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let mut crc = [0; 4];
+    reader.read_exact(&mut crc)?;
+    bytes.extend_from_slice(&data);
+    if crc32(&bytes) != u32::from_le_bytes(crc) {
+        return Err(Error::Checksum);
+    }
+    if header.hash_kernels != DEFAULT_HASH_KERNELS {
+        return Err(Error::HashKernels(header.hash_kernels));
+    }
+    Ok((header, data))
+}
+
+/// Creates (or truncates) the file at `path` for the buckets of a filter and
+/// maps it in memory, the buckets are zeroed.
+#[cfg(feature = "mmap")]
+pub(crate) fn create_mmap(path: &Path, header: &Header) -> Result<MmapMut, Error> {
+    if header.bucket_size == 0 || header.bucket_size >= 8 {
+        return Err(Error::Inconsistent("bucket size must be in [1, 7]"));
+    }
+    let words = header
+        .count
+        .checked_mul(header.bucket_size as usize)
+        .map(|bits| (bits + 63) / 64);
+    let len = words
+        .and_then(|words| words.checked_mul(8))
+        .ok_or(Error::Inconsistent("value overflows usize"))?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    file.set_len((HEADER_LEN + len) as u64)?;
+    // safety: the file is truncated above, other processes must not modify it
+    let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+    mmap[..HEADER_LEN].copy_from_slice(&encode_header(MMAP_MAGIC, header, len));
+    Ok(mmap)
+}
+
+/// Maps in memory a file created by `create_mmap` for a filter of `kind`.
+#[cfg(feature = "mmap")]
+pub(crate) fn open_mmap(path: &Path, kind: u8) -> Result<(Header, MmapMut), Error> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    // safety: other processes must not modify the file while it's mapped
+    let mmap = unsafe { MmapMut::map_mut(&file)? };
+    if mmap.len() < HEADER_LEN {
+        return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let (header, len) = decode_header(&mmap[..HEADER_LEN], MMAP_MAGIC, kind)?;
+    if mmap.len() != HEADER_LEN + len {
+        return Err(Error::Inconsistent("file length doesn't match the buckets count"));
+    }
+    if header.hash_kernels != DEFAULT_HASH_KERNELS {
+        return Err(Error::HashKernels(header.hash_kernels));
+    }
+    Ok((header, mmap))
+}
+
+fn encode_header(magic: [u8; 4], header: &Header, len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    bytes.extend_from_slice(&magic);
+    bytes.extend_from_slice(&[VERSION, header.kind, header.hash_kernels, header.bucket_size]);
+    for value in &[header.count, header.k, header.hash_seed, header.p, len] {
+        bytes.extend_from_slice(&(*value as u64).to_le_bytes());
+    }
+    bytes
+}
+
+// returns the header and the length of the data
+fn decode_header(bytes: &[u8], magic: [u8; 4], kind: u8) -> Result<(Header, usize), Error> {
+    if bytes[..4] != magic {
+        return Err(Error::Magic);
+    }
+    if bytes[4] != VERSION {
+        return Err(Error::Version(bytes[4]));
+    }
+    if bytes[5] != kind {
+        return Err(Error::Kind(bytes[5]));
+    }
+    let field = |i: usize| {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[8 + i * 8..16 + i * 8]);
+        u64::from_le_bytes(buf)
+    };
+    let (count, k, hash_seed, p, len) = (field(0), field(1), field(2), field(3), field(4));
+    let header = Header {
+        kind,
+        hash_kernels: bytes[6],
+        bucket_size: bytes[7],
+        count: to_usize(count)?,
+        k: to_usize(k)?,
+        hash_seed: to_usize(hash_seed)?,
+        p: to_usize(p)?,
+    };
+    if header.bucket_size == 0 || header.bucket_size >= 8 {
+        return Err(Error::Inconsistent("bucket size must be in [1, 7]"));
+    }
+    if header.count == 0 || header.k == 0 {
+        return Err(Error::Inconsistent("count and k must be positive"));
+    }
+    let words = count.checked_mul(u64::from(header.bucket_size)).map(|bits| (bits + 63) / 64);
+    if words.and_then(|words| words.checked_mul(8)) != Some(len) {
+        return Err(Error::Inconsistent("data length doesn't match the buckets count"));
+    }
+    Ok((header, to_usize(len)?))
+}
+
+pub(crate) fn to_usize(value: u64) -> Result<usize, Error> {
+    usize::try_from(value).map_err(|_| Error::Inconsistent("value overflows usize"))
+}
+
+// CRC-32 (IEEE 802.3), bitwise
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            kind: CLASSIC,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: 1,
+            count: 64,
+            k: 3,
+            hash_seed: 42,
+            p: 0,
+        }
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(0, crc32(b""));
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn read_write() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &header(), &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(HEADER_LEN + 8 + 4, bytes.len());
+        let (header, data) = read(&bytes[..], CLASSIC).unwrap();
+        assert_eq!((64, 3, 42), (header.count, header.k, header.hash_seed));
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], data);
+
+        assert!(matches!(read(&bytes[..], STABLE), Err(Error::Kind(CLASSIC))));
+        assert!(matches!(read(&bytes[..20], CLASSIC), Err(Error::Io(_))));
+        assert!(matches!(read(&bytes[..HEADER_LEN + 4], CLASSIC), Err(Error::Io(_))));
+        let mut corrupted = bytes.clone();
+        corrupted[50] ^= 1;
+        assert!(matches!(read(&corrupted[..], CLASSIC), Err(Error::Checksum)));
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert!(matches!(read(&corrupted[..], CLASSIC), Err(Error::Magic)));
+        let mut corrupted = bytes;
+        corrupted[4] = 2;
+        assert!(matches!(read(&corrupted[..], CLASSIC), Err(Error::Version(2))));
+    }
+
+    #[test]
+    fn inconsistent() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &header(), &[0; 16]).unwrap();
+        assert!(matches!(read(&bytes[..], CLASSIC), Err(Error::Inconsistent(_))));
+
+        let mut bytes = Vec::new();
+        let header = Header {
+            bucket_size: 8,
+            ..header()
+        };
+        write(&mut bytes, &header, &[0; 64]).unwrap();
+        assert!(matches!(read(&bytes[..], CLASSIC), Err(Error::Inconsistent(_))));
+    }
+
+    #[test]
+    fn huge_length() {
+        // a header announcing 2^60 bytes of data followed by a few bytes
+        let header = Header {
+            count: 1 << 63,
+            ..header()
+        };
+        let mut bytes = encode_header(MAGIC, &header, 1 << 60);
+        bytes.extend_from_slice(&[0; 12]);
+        assert!(matches!(read(&bytes[..], CLASSIC), Err(Error::Io(_))));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filter");
+        let mut mmap = create_mmap(&path, &header()).unwrap();
+        assert_eq!(HEADER_LEN + 8, mmap.len());
+        mmap[HEADER_LEN] = 1;
+        mmap.flush().unwrap();
+        drop(mmap);
+
+        let (opened, mmap) = open_mmap(&path, CLASSIC).unwrap();
+        assert_eq!((64, 3, 42), (opened.count, opened.k, opened.hash_seed));
+        assert_eq!(1, mmap[HEADER_LEN]);
+        drop(mmap);
+        assert!(matches!(open_mmap(&path, COUNTING), Err(Error::Kind(CLASSIC))));
+        assert!(matches!(
+            read(std::fs::File::open(&path).unwrap(), CLASSIC),
+            Err(Error::Magic)
+        ));
+
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(HEADER_LEN as u64 + 16).unwrap();
+        assert!(matches!(open_mmap(&path, CLASSIC), Err(Error::Inconsistent(_))));
+        file.set_len(20).unwrap();
+        assert!(matches!(open_mmap(&path, CLASSIC), Err(Error::Io(_))));
+
+        let header = Header {
+            bucket_size: 0,
+            ..header()
+        };
+        assert!(matches!(create_mmap(&path, &header), Err(Error::Inconsistent(_))));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::buckets::Buckets;
+use crate::{BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+/// A frequency sketch with small saturating counters. Inserts use conservative
+/// update, only the smallest counters of an item are incremented, and all
+/// counters are halved every `sample_size` inserts so that estimations favor
+/// recent items.
+pub struct Sketch<BHK: BuildHashKernels> {
+    buckets: Buckets,      // counters
+    hash_kernels: BHK::HK, // hash kernels
+    sample_size: usize,    // inserts between two decays, 0 means never decay
+    inserts: usize,        // inserts since the last decay
+}
+
+impl<BHK: BuildHashKernels> Sketch<BHK> {
+    /// Create a new frequency sketch structure.
+    /// items_count is an estimation of the number of distinct items in a sample.
+    /// bucket_size is the specified number of bits of counters
+    /// fp_rate is the wanted rate of overestimated items, in ]0.0, 1.0[
+    /// sample_size is the number of inserts between two decays, 0 disables decaying
+    pub fn new(items_count: usize, bucket_size: u8, fp_rate: f64, sample_size: usize, build_hash_kernels: BHK) -> Self {
+        let buckets = Buckets::with_fp_rate(items_count, fp_rate, bucket_size);
+        let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len());
+        Self {
+            buckets,
+            hash_kernels,
+            sample_size,
+            inserts: 0,
+        }
+    }
+
+    pub fn insert<T: Hash>(&mut self, item: &T) {
+        let min = self.estimate(item);
+        if min < self.buckets.max_value() {
+            let buckets = &mut self.buckets;
+            self.hash_kernels.hash_iter(item).for_each(|i| {
+                if buckets.get(i) == min {
+                    buckets.set(i, min + 1)
+                }
+            });
+        }
+
+        self.inserts += 1;
+        if self.sample_size > 0 && self.inserts >= self.sample_size {
+            self.decay();
+        }
+    }
+
+    /// Returns the estimated frequency of item, which is never underestimated
+    /// since the last decay.
+    pub fn estimate<T: Hash>(&self, item: &T) -> u8 {
+        self.hash_kernels
+            .hash_iter(item)
+            .map(|i| self.buckets.get(i))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Halves all counters, it is triggered every `sample_size` inserts.
+    pub fn decay(&mut self) {
+        (0..self.buckets.len()).for_each(|i| {
+            let value = self.buckets.get(i);
+            if value > 0 {
+                self.buckets.set(i, value >> 1)
+            }
+        });
+        self.inserts /= 2;
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.reset();
+        self.inserts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+    use std::collections::HashMap;
+
+    fn _estimate(items: &[u8]) {
+        let mut sketch = Sketch::new(100, 7, 0.01, 0, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        let mut counts = HashMap::new();
+        items.iter().for_each(|i| {
+            sketch.insert(i);
+            *counts.entry(i).or_insert(0u8) += 1;
+        });
+        assert!(counts.iter().all(|(i, count)| sketch.estimate(i) >= *count));
+    }
+
+    proptest! {
+        #[test]
+        fn estimate(ref items in any_with::<Vec<u8>>(size_range(64).lift())) {
+            _estimate(items)
+        }
+    }
+
+    #[test]
+    fn decay() {
+        let mut sketch = Sketch::new(100, 4, 0.01, 0, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        (0..20).for_each(|_| sketch.insert(&"hot"));
+        (0..6).for_each(|_| sketch.insert(&"warm"));
+        // saturated at 15
+        assert_eq!(15, sketch.estimate(&"hot"));
+        assert_eq!(6, sketch.estimate(&"warm"));
+
+        sketch.decay();
+        assert_eq!(7, sketch.estimate(&"hot"));
+        assert_eq!(3, sketch.estimate(&"warm"));
+    }
+
+    #[test]
+    fn sample_size() {
+        let mut sketch = Sketch::new(100, 4, 0.01, 10, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        (0..9).for_each(|_| sketch.insert(&"item"));
+        assert_eq!(9, sketch.estimate(&"item"));
+        // the 10th insert triggers the decay
+        sketch.insert(&"item");
+        assert_eq!(5, sketch.estimate(&"item"));
+        // 5 inserts remain in the sample
+        (0..4).for_each(|_| sketch.insert(&"item"));
+        assert_eq!(9, sketch.estimate(&"item"));
+        sketch.insert(&"item");
+        assert_eq!(5, sketch.estimate(&"item"));
+    }
+}
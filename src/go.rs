@@ -0,0 +1,156 @@
+//! Compatibility with the `WriteTo`/`ReadFrom` binary format of the Go
+//! [BoomFilters](https://github.com/tylertreat/BoomFilters) library, which
+//! `GobEncode`/`GobDecode` also use.
+//!
+//! BoomFilters hashes the item bytes with 64 bits FNV-1 and derives the k
+//! positions as `(lower + upper * i) % m` from the lower and upper 32 bits of
+//! the hash, which are the positions of `DefaultHashKernels` with a hash seed
+//! of 0 and [`BuildFnv1Hasher`]. Items must be wrapped in [`GoBytes`] to be
+//! hashed as Go does.
+//!
+//! All integers are big-endian:
+//!
+//! | filter   | fields                    |
+//! |----------|---------------------------|
+//! | classic  | count, m, k, buckets      |
+//! | counting | count, m, k, buckets      |
+//! | stable   | m, p, k, buckets          |
+//!
+//! count, m, k and p are `u64`, buckets are the bucket size (`u8`), the max
+//! bucket value (`u8`), the buckets count (`u64`), the data length (`u64`)
+//! followed by `(count * bucket_size + 7) / 8` bytes of data.
+//!
+//! The layout follows the Go source, the test vectors aren't yet output of
+//! the library, `testdata/boomfilters/main.go` generates them.
+
+use crate::buckets::Buckets;
+use crate::format::{to_usize, Error};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Read, Write};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64 bits FNV-1 hasher, the `fnv.New64` hash of Go.
+pub struct Fnv1Hasher(u64);
+
+impl Default for Fnv1Hasher {
+    fn default() -> Self {
+        Fnv1Hasher(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes
+            .iter()
+            .fold(self.0, |hash, byte| hash.wrapping_mul(FNV_PRIME) ^ u64::from(*byte));
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Used to create a Fnv1Hasher instance.
+#[derive(Clone, Default)]
+pub struct BuildFnv1Hasher;
+
+impl BuildHasher for BuildFnv1Hasher {
+    type Hasher = Fnv1Hasher;
+
+    fn build_hasher(&self) -> Fnv1Hasher {
+        Fnv1Hasher::default()
+    }
+}
+
+/// Bytes hashed without length prefix, as Go hashes a `[]byte`.
+pub struct GoBytes<'a>(pub &'a [u8]);
+
+impl<'a> Hash for GoBytes<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.0)
+    }
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: usize) -> Result<(), Error> {
+    writer.write_all(&(value as u64).to_be_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<usize, Error> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    to_usize(u64::from_be_bytes(buf))
+}
+
+pub(crate) fn write_buckets<W: Write>(writer: &mut W, buckets: &Buckets) -> Result<(), Error> {
+    let len = (buckets.len() * buckets.bucket_size() as usize + 7) / 8;
+    writer.write_all(&[buckets.bucket_size(), buckets.max_value()])?;
+    write_u64(writer, buckets.len())?;
+    write_u64(writer, len)?;
+    writer.write_all(&buckets.raw_data()[..len])?;
+    Ok(())
+}
+
+pub(crate) fn read_buckets<R: Read>(reader: &mut R) -> Result<Buckets, Error> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    let (bucket_size, max) = (buf[0], buf[1]);
+    if bucket_size == 0 || bucket_size >= 8 || max != (1 << bucket_size) - 1 {
+        return Err(Error::Inconsistent("bucket size must be in [1, 7]"));
+    }
+    let count = read_u64(reader)?;
+    let len = read_u64(reader)?;
+    let bits = count.checked_mul(bucket_size as usize);
+    if count == 0 || bits.map(|bits| (bits + 7) / 8) != Some(len) {
+        return Err(Error::Inconsistent("data length doesn't match the buckets count"));
+    }
+    // the length isn't trusted, so the data is read as it comes
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len {
+        return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    // Go buckets are byte aligned, `Buckets` are word aligned
+    data.resize((len + 7) / 8 * 8, 0);
+    Ok(Buckets::with_raw_data(count, bucket_size, &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv1(bytes: &[u8]) -> u64 {
+        let mut hasher = Fnv1Hasher::default();
+        GoBytes(bytes).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn fnv() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1(b""));
+        assert_eq!(0xaf63_bd4c_8601_b7be, fnv1(b"a"));
+    }
+
+    #[test]
+    fn buckets() {
+        let mut buckets = Buckets::new(20, 3);
+        buckets.set(0, 1);
+        buckets.set(19, 7);
+        let mut bytes = Vec::new();
+        write_buckets(&mut bytes, &buckets).unwrap();
+        assert_eq!(2 + 8 + 8 + 8, bytes.len());
+        let buckets = read_buckets(&mut &bytes[..]).unwrap();
+        assert_eq!((20, 1, 7), (buckets.len(), buckets.get(0), buckets.get(19)));
+
+        assert!(matches!(read_buckets(&mut &bytes[..25]), Err(Error::Io(_))));
+        bytes[1] = 3;
+        assert!(matches!(read_buckets(&mut &bytes[..]), Err(Error::Inconsistent(_))));
+
+        // 2^60 buckets announced, a few bytes present
+        let mut bytes = vec![1, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(matches!(read_buckets(&mut &bytes[..]), Err(Error::Io(_))));
+    }
+}
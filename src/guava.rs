@@ -0,0 +1,271 @@
+use crate::format::{to_usize, Error};
+use std::f64::consts::LN_2;
+use std::io::{self, Read, Write};
+
+// ordinal of the MURMUR128_MITZ_64 strategy
+const MURMUR128_MITZ_64: u8 = 1;
+
+/// Bloom filter of Guava with the `MURMUR128_MITZ_64` strategy, the k indexes
+/// of an item are derived from the two halves of its 128 bits MurmurHash3.
+/// Items are the bytes hashed by the funnel: `Funnels.byteArrayFunnel()`
+/// hashes the array and `Funnels.stringFunnel(UTF_8)` the UTF-8 bytes of the
+/// string, so `str::as_bytes` gives the same indexes.
+pub struct Filter {
+    data: Vec<u64>,         // bits, bit i is bit i % 64 of word i / 64
+    num_hash_functions: u8, // number of hash functions
+}
+
+impl Filter {
+    /// Create a new Guava bloom filter structure, sized as `BloomFilter.create` does.
+    /// expected_insertions is an estimation of the maximum number of items to store.
+    /// fpp is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(expected_insertions: usize, fpp: f64) -> Self {
+        debug_assert!(fpp > 0.0 && fpp < 1.0);
+        let n = expected_insertions.max(1) as f64;
+        let bits = (-n * fpp.ln() / (LN_2 * LN_2)) as u64;
+        // as recent Guava releases do, older ones used `bits / n * ln 2`
+        let num_hash_functions = ((-fpp.ln() / LN_2).round() as u8).max(1);
+        // Guava rejects filters without bits
+        let words = ((bits + 63) / 64).max(1) as usize;
+        Self {
+            data: vec![0; words],
+            num_hash_functions,
+        }
+    }
+
+    pub fn data(&self) -> &[u64] {
+        &self.data
+    }
+
+    pub fn num_hash_functions(&self) -> u8 {
+        self.num_hash_functions
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let data = &mut self.data;
+        indexes(item, data.len(), self.num_hash_functions).for_each(|i| data[i / 64] |= 1 << (i % 64))
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        indexes(item, self.data.len(), self.num_hash_functions).all(|i| self.data[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    pub fn reset(&mut self) {
+        self.data.iter_mut().for_each(|x| *x = 0)
+    }
+
+    /// Writes the filter as `BloomFilter.writeTo` does: the strategy ordinal,
+    /// the number of hash functions, the number of words as a big-endian `i32`
+    /// and the big-endian words.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(6 + self.data.len() * 8);
+        bytes.extend_from_slice(&[MURMUR128_MITZ_64, self.num_hash_functions]);
+        bytes.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+        self.data.iter().for_each(|word| bytes.extend_from_slice(&word.to_be_bytes()));
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Reads a filter written by `BloomFilter.writeTo`, other strategies than
+    /// `MURMUR128_MITZ_64` are rejected.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0; 6];
+        reader.read_exact(&mut header)?;
+        if header[0] != MURMUR128_MITZ_64 {
+            return Err(Error::HashKernels(header[0]));
+        }
+        let words = i32::from_be_bytes([header[2], header[3], header[4], header[5]]);
+        if header[1] == 0 || words <= 0 {
+            return Err(Error::Inconsistent("hash functions and data length must be positive"));
+        }
+        // the word count isn't trusted, so the words are read as they come
+        let len = to_usize(words as u64 * 8)?;
+        let mut bytes = Vec::new();
+        reader.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let data = bytes
+            .chunks_exact(8)
+            .map(|chunk| {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(chunk);
+                u64::from_be_bytes(buf)
+            })
+            .collect();
+        Ok(Self {
+            data,
+            num_hash_functions: header[1],
+        })
+    }
+}
+
+// returns the bit indexes of item in a filter of `words` words
+fn indexes(item: &[u8], words: usize, num_hash_functions: u8) -> impl Iterator<Item = usize> {
+    let bits = words as u64 * 64;
+    let (h1, h2) = murmur3_128(item);
+    let mut combined = h1;
+    (0..num_hash_functions).map(move |_| {
+        // made positive as a java long
+        let index = (combined & i64::MAX as u64) % bits;
+        combined = combined.wrapping_add(h2);
+        index as usize
+    })
+}
+
+// MurmurHash3 x64 128 bits without seed, returns the lower and upper halves
+fn murmur3_128(data: &[u8]) -> (u64, u64) {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    let mix_k1 = |k: u64| k.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    let mix_k2 = |k: u64| k.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    let fmix = |k: u64| {
+        let k = (k ^ (k >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        let k = (k ^ (k >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        k ^ (k >> 33)
+    };
+    let read = |bytes: &[u8]| bytes.iter().rev().fold(0, |k, byte| k << 8 | u64::from(*byte));
+
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let mut chunks = data.chunks_exact(16);
+    for chunk in chunks.by_ref() {
+        h1 ^= mix_k1(read(&chunk[..8]));
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dc_e729);
+        h2 ^= mix_k2(read(&chunk[8..]));
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x3849_5ab5);
+    }
+    let tail = chunks.remainder();
+    if tail.len() > 8 {
+        h2 ^= mix_k2(read(&tail[8..]));
+    }
+    if !tail.is_empty() {
+        h1 ^= mix_k1(read(&tail[..tail.len().min(8)]));
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix(h1);
+    h2 = fmix(h2);
+    h1 = h1.wrapping_add(h2);
+    (h1, h2.wrapping_add(h1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len() / 2)
+            .map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+            .collect()
+    }
+
+    // written by Guava 33.4.0
+    const STRINGS: &str = "01070000000200100410090410048404044444042044";
+    const BYTES: &str = "010a00000002491554a65a011751c101604331b11014";
+
+    fn _contains(items: &[Vec<u8>]) {
+        let mut filter = Filter::new(items.len(), 0.03);
+        items.iter().for_each(|i| filter.insert(i));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<Vec<u8>>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    // `Hashing.murmur3_128().hashBytes(..).asBytes()` of the prefixes of the bytes `i * 7 + 3`
+    #[test]
+    fn murmur3_vectors() {
+        let input = (0..100).map(|i| (i * 7 + 3) as u8).collect::<Vec<_>>();
+        let vectors = [
+            (0, "00000000000000000000000000000000"),
+            (1, "593e6a30ddc66a72e4a8b5c52711714e"),
+            (7, "a1e36c2354bfcbbe50ef9ce392a43b2b"),
+            (8, "bdd332919a2aeea53a007e613a7f9e25"),
+            (15, "f4e4ad805e4b6aba6df2e8f78f5a0ee0"),
+            (16, "a14e8f2fc599b0c448fe2ad91902677d"),
+            (17, "27b153fe394baed4e9db81663b450266"),
+            (31, "fb3604f0dffe919dd0bf7e73ba51a87e"),
+            (32, "ff430608ddb8bd650a91f3a58a1bc3be"),
+            (100, "d3a475b6a2526a172a281c38700baca2"),
+        ];
+        for (len, expected) in vectors.iter() {
+            let (h1, h2) = murmur3_128(&input[..*len]);
+            assert_eq!(hex(expected), [h1.to_le_bytes(), h2.to_le_bytes()].concat());
+        }
+        let (h1, h2) = murmur3_128(b"The quick brown fox jumps over the lazy dog");
+        assert_eq!((0xe34b_bc7b_bc07_1b6c, 0x7a43_3ca9_c49a_9347), (h1, h2));
+    }
+
+    #[test]
+    fn string_funnel() {
+        let mut filter = Filter::new(10, 0.01);
+        ["hello", "world", "ünïcödé"].iter().for_each(|s| filter.insert(s.as_bytes()));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        assert_eq!(hex(STRINGS), bytes);
+
+        let filter = Filter::read_from(&hex(STRINGS)[..]).unwrap();
+        assert!(["hello", "world", "ünïcödé"].iter().all(|s| filter.contains(s.as_bytes())));
+        assert!(["bloom", "filter", ""].iter().all(|s| !filter.contains(s.as_bytes())));
+    }
+
+    #[test]
+    fn byte_array_funnel() {
+        let mut filter = Filter::new(5, 0.001);
+        (0..5).for_each(|i| filter.insert(&[i, 0x80 + i]));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        assert_eq!(hex(BYTES), bytes);
+
+        let filter = Filter::read_from(&hex(BYTES)[..]).unwrap();
+        assert!((0..5).all(|i| filter.contains(&[i, 0x80 + i])));
+        assert!((5..8).all(|i| !filter.contains(&[i, 0x80 + i])));
+    }
+
+    #[test]
+    fn sizes() {
+        let sizes = [
+            (1000, 0.03, 5, 115),
+            (1, 0.5, 1, 1),
+            (0, 0.01, 7, 1),
+            (100_000, 0.01, 7, 14977),
+        ];
+        for (n, fpp, k, words) in sizes.iter() {
+            let filter = Filter::new(*n, *fpp);
+            assert_eq!((*k, *words), (filter.num_hash_functions(), filter.data().len()));
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut bytes = hex(STRINGS);
+        bytes[0] = 0;
+        assert!(matches!(Filter::read_from(&bytes[..]), Err(Error::HashKernels(0))));
+        assert!(matches!(
+            Filter::read_from(&hex("010000000001")[..]),
+            Err(Error::Inconsistent(_))
+        ));
+        assert!(matches!(
+            Filter::read_from(&hex("0107ffffffff")[..]),
+            Err(Error::Inconsistent(_))
+        ));
+        assert!(matches!(Filter::read_from(&hex(STRINGS)[..12]), Err(Error::Io(_))));
+        // i32::MAX words announced, a few bytes present
+        assert!(matches!(
+            Filter::read_from(&hex("01077fffffff0000000000000000")[..]),
+            Err(Error::Io(_))
+        ));
+        assert!(matches!(
+            Filter::read_from(&hex("010780000000")[..]),
+            Err(Error::Inconsistent(_))
+        ));
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{BuildHasher, Hash, Hasher};
 
@@ -30,6 +32,7 @@ where
 }
 
 /// Used to create a DefaultHashKernels instance.
+#[derive(Clone)]
 pub struct DefaultBuildHashKernels<BH> {
     hash_seed: usize,
     build_hasher: BH,
@@ -55,6 +58,10 @@ impl<BH: BuildHasher> BuildHashKernels for DefaultBuildHashKernels<BH> {
 }
 
 /// A default implementation of [Kirsch-Mitzenmacher-Optimization](https://www.eecs.harvard.edu/~michaelm/postscripts/tr-02-05.pdf) hash function
+///
+/// With the `serde` feature the kernels are serializable when the hasher is,
+/// hashers which can't be restored, like `RandomState`, don't implement `Serialize`.
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct DefaultHashKernels<BH> {
     k: usize,         // numbers of hash iterating
     n: usize,         // filter size
@@ -62,6 +69,52 @@ pub struct DefaultHashKernels<BH> {
     build_hasher: BH,
 }
 
+impl<BH> DefaultHashKernels<BH> {
+    pub(crate) fn k(&self) -> usize {
+        self.k
+    }
+
+    // filter size, must match the buckets count of the filter
+    #[cfg(feature = "serde")]
+    pub(crate) fn len(&self) -> usize {
+        self.n
+    }
+
+    pub(crate) fn hash_seed(&self) -> usize {
+        self.hash_seed
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, BH: Deserialize<'de>> Deserialize<'de> for DefaultHashKernels<BH> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "DefaultHashKernels")]
+        struct Kernels<BH> {
+            k: usize,
+            n: usize,
+            hash_seed: usize,
+            build_hasher: BH,
+        }
+
+        let Kernels {
+            k,
+            n,
+            hash_seed,
+            build_hasher,
+        } = Kernels::deserialize(deserializer)?;
+        if k == 0 || n == 0 {
+            return Err(D::Error::custom("hash kernels k and n must be positive"));
+        }
+        Ok(Self {
+            k,
+            n,
+            hash_seed,
+            build_hasher,
+        })
+    }
+}
+
 impl<BH: BuildHasher> HashKernels for DefaultHashKernels<BH> {
     type HI = DefaultHashIter;
 
@@ -113,6 +166,8 @@ impl Iterator for DefaultHashIter {
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DefaultBuildHasher;
 
 impl BuildHasher for DefaultBuildHasher {
@@ -0,0 +1,201 @@
+use crate::buckets::Buckets;
+use crate::{BuildHashKernels, HashKernels};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A Bloom filter index answering which of many classic bloom filters may
+/// contain an item. The filters are the leaves of a tree whose inner nodes
+/// are the `BitOr` of their children, a query only descends into the nodes
+/// containing the item.
+///
+/// All filters must be created with the same buckets count, k and hash kernels.
+pub struct Index<BHK: BuildHashKernels> {
+    levels: Vec<Vec<Buckets>>,    // levels[0] are the filters, the last level is the root
+    ids: Vec<usize>,              // filter id of each leaf
+    slots: HashMap<usize, usize>, // leaf of each filter id
+    bucket_count: usize,          // buckets count of filters
+    order: usize,                 // max children count of nodes
+    hash_kernels: BHK::HK,        // hash kernels shared by all filters
+}
+
+impl<BHK: BuildHashKernels> Index<BHK> {
+    /// Create a new index of classic bloom filters with bucket_count buckets
+    /// and k hash functions, inner nodes have at most order children.
+    pub fn new(bucket_count: usize, k: usize, order: usize, build_hash_kernels: BHK) -> Self {
+        debug_assert!(order > 1);
+        Self {
+            levels: vec![Vec::new()],
+            ids: Vec::new(),
+            slots: HashMap::new(),
+            bucket_count,
+            order,
+            hash_kernels: build_hash_kernels.with_k(k, bucket_count),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Inserts the `raw_data` of a filter, replacing the filter with the same id.
+    pub fn insert(&mut self, id: usize, raw_data: &[u8]) {
+        let buckets = Buckets::with_raw_data(self.bucket_count, 1, raw_data);
+        let slot = match self.slots.get(&id) {
+            Some(slot) => {
+                self.levels[0][*slot] = buckets;
+                *slot
+            }
+            None => {
+                self.levels[0].push(buckets);
+                self.ids.push(id);
+                self.slots.insert(id, self.ids.len() - 1);
+                self.resize();
+                self.ids.len() - 1
+            }
+        };
+        self.refresh(slot);
+    }
+
+    /// Removes a filter, returns whether it was in the index.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let slot = match self.slots.remove(&id) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        // the last leaf fills the hole
+        let last = self.ids.len() - 1;
+        self.levels[0].swap_remove(slot);
+        self.ids.swap_remove(slot);
+        if slot < last {
+            self.slots.insert(self.ids[slot], slot);
+        }
+        self.resize();
+        if slot < last {
+            self.refresh(slot);
+        }
+        if last > 0 {
+            self.refresh(last - 1);
+        }
+        true
+    }
+
+    /// Returns the ids of the filters which may contain item.
+    pub fn candidates<T: Hash>(&self, item: &T) -> Vec<usize> {
+        let positions = self.hash_kernels.hash_iter(item).collect::<Vec<_>>();
+        let mut result = Vec::new();
+        let top = self.levels.len() - 1;
+        (0..self.levels[top].len()).for_each(|node| self.search(top, node, &positions, &mut result));
+        result
+    }
+
+    fn search(&self, level: usize, node: usize, positions: &[usize], result: &mut Vec<usize>) {
+        let buckets = &self.levels[level][node];
+        if !positions.iter().all(|i| buckets.get(*i) == 1) {
+            return;
+        }
+        if level == 0 {
+            result.push(self.ids[node]);
+        } else {
+            let end = ((node + 1) * self.order).min(self.levels[level - 1].len());
+            (node * self.order..end).for_each(|child| self.search(level - 1, child, positions, result));
+        }
+    }
+
+    // adjusts the nodes count of inner levels to the leaves count
+    fn resize(&mut self) {
+        let mut len = self.levels[0].len();
+        let mut level = 1;
+        while len > 1 {
+            len = (len + self.order - 1) / self.order;
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let bucket_count = self.bucket_count;
+            self.levels[level].resize_with(len, || Buckets::new(bucket_count, 1));
+            level += 1;
+        }
+        self.levels.truncate(level);
+    }
+
+    // recomputes the ancestors of a leaf
+    fn refresh(&mut self, slot: usize) {
+        let mut node = slot;
+        for level in 1..self.levels.len() {
+            node /= self.order;
+            let end = ((node + 1) * self.order).min(self.levels[level - 1].len());
+            let (children, parents) = self.levels.split_at_mut(level);
+            let parent = &mut parents[0][node];
+            parent.reset();
+            children[level - 1][node * self.order..end]
+                .iter()
+                .for_each(|child| parent.union(child));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classic::Filter as ClassicFilter;
+    use crate::hash::{DefaultBuildHashKernels, DefaultBuildHasher};
+    use crate::BloomFilter;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+
+    const BYTES: usize = 64;
+    const K: usize = 3;
+
+    fn filter(hash_seed: usize, items: &[usize]) -> Vec<u8> {
+        let build_hash_kernels = DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher);
+        let mut filter = ClassicFilter::with_raw_data(&[0; BYTES], K, build_hash_kernels);
+        items.iter().for_each(|i| filter.insert(i));
+        filter.buckets().raw_data()
+    }
+
+    fn _candidates(items: &[usize]) {
+        let hash_seed = random();
+        let mut index = Index::new(BYTES * 8, K, 4, DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher));
+        items
+            .iter()
+            .enumerate()
+            .for_each(|(id, i)| index.insert(id, &filter(hash_seed, &[*i])));
+        assert_eq!(items.len(), index.len());
+        assert!(items.iter().enumerate().all(|(id, i)| index.candidates(i).contains(&id)));
+    }
+
+    proptest! {
+        #[test]
+        fn candidates(ref items in any_with::<Vec<usize>>(size_range(64).lift())) {
+            _candidates(items)
+        }
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let hash_seed = random();
+        let mut index = Index::new(BYTES * 8, K, 2, DefaultBuildHashKernels::new(hash_seed, DefaultBuildHasher));
+        assert!(index.candidates(&1usize).is_empty());
+        (0..10).for_each(|id| index.insert(id, &filter(hash_seed, &[id * 100])));
+        assert!(index.candidates(&300usize).contains(&3));
+        assert!(index.candidates(&900usize).contains(&9));
+
+        index.insert(3, &filter(hash_seed, &[301]));
+        assert!(index.candidates(&301usize).contains(&3));
+        assert!(!index.candidates(&300usize).contains(&3));
+
+        assert!(index.remove(0));
+        assert!(!index.remove(0));
+        assert_eq!(9, index.len());
+        assert!(!index.candidates(&0usize).contains(&0));
+        assert!(index.candidates(&900usize).contains(&9));
+        (1..10).for_each(|id| {
+            assert!(index.remove(id));
+        });
+        assert!(index.is_empty());
+        assert!(index.candidates(&900usize).is_empty());
+    }
+}
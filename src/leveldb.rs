@@ -0,0 +1,172 @@
+// seed of the hash of keys
+const SEED: u32 = 0xbc9f_1d34;
+// max number of probes, larger values are reserved by LevelDB
+const MAX_PROBES: u8 = 30;
+
+/// Filter block of the `leveldb.BuiltinBloomFilter2` policy of LevelDB, the
+/// bits followed by the number of probes in the last byte. The positions of a
+/// key are derived from one 32 bits hash with double hashing.
+pub struct Filter {
+    data: Vec<u8>, // filter block
+}
+
+impl Filter {
+    /// Create a new filter block, sized as `NewBloomFilterPolicy` does.
+    /// keys_count is the number of keys of the block.
+    /// bits_per_key is the parameter of the policy, usually 10
+    pub fn new(keys_count: usize, bits_per_key: usize) -> Self {
+        // round down to reduce probing cost, as LevelDB does
+        let k = ((bits_per_key as f64 * 0.69) as u8).clamp(1, MAX_PROBES);
+        // a minimum length avoids a very high false positive rate for small counts
+        let bits = (keys_count * bits_per_key).max(64);
+        let mut data = vec![0; (bits + 7) / 8 + 1];
+        *data.last_mut().unwrap() = k;
+        Self { data }
+    }
+
+    /// Uses a filter block read from a table file.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Inserts a key, blocks of reserved encodings ignore the inserts.
+    pub fn insert(&mut self, key: &[u8]) {
+        if self.data.len() < 2 || self.data[self.data.len() - 1] > MAX_PROBES {
+            return;
+        }
+        for i in self.positions(key) {
+            self.data[i / 8] |= 1 << (i % 8);
+        }
+    }
+
+    /// Returns whether the key may be in the block, as `KeyMayMatch` does.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        if self.data.len() < 2 {
+            return false;
+        }
+        // reserved for new encodings of short filters, consider it a match
+        if self.data[self.data.len() - 1] > MAX_PROBES {
+            return true;
+        }
+        self.positions(key).all(|i| self.data[i / 8] & (1 << (i % 8)) != 0)
+    }
+
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let bits = (self.data.len() - 1) * 8;
+        let mut h = hash(key, SEED, false);
+        let delta = h.rotate_right(17);
+        (0..self.data[self.data.len() - 1]).map(move |_| {
+            let position = h as usize % bits;
+            h = h.wrapping_add(delta);
+            position
+        })
+    }
+}
+
+/// Hash of LevelDB, similar to MurmurHash1. RocksDB sign-extends the trailing
+/// bytes, as the original implementation did with a signed `char`.
+pub(crate) fn hash(data: &[u8], seed: u32, sign_extend: bool) -> u32 {
+    const M: u32 = 0xc6a4_a793;
+    let mut chunks = data.chunks_exact(4);
+    let mut h = chunks.by_ref().fold(seed ^ (data.len() as u32).wrapping_mul(M), |h, chunk| {
+        let w = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let h = h.wrapping_add(w).wrapping_mul(M);
+        h ^ (h >> 16)
+    });
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate().rev() {
+            let byte = if sign_extend { *byte as i8 as u32 } else { u32::from(*byte) };
+            h = h.wrapping_add(byte << (8 * i));
+        }
+        h = h.wrapping_mul(M);
+        h ^= h >> 24;
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len() / 2)
+            .map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn _contains(items: &[Vec<u8>]) {
+        let mut filter = Filter::new(items.len(), 10);
+        items.iter().for_each(|i| filter.insert(i));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<Vec<u8>>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    // hashes of the prefixes of e180b932c397e2 by LevelDB 1.22 and RocksDB
+    #[test]
+    fn hash_vectors() {
+        let data = hex("e180b932c397e2");
+        let leveldb = [
+            0xbc9f_1d34,
+            0x7ac2_6762,
+            0xd7a3_a65e,
+            0x6eb6_0544,
+            0xed21_633a,
+            0xf913_c990,
+            0x992d_0506,
+            0xc648_3bc3,
+        ];
+        let rocksdb = [
+            0xbc9f_1d34,
+            0xd61a_d4ce,
+            0x8b69_1302,
+            0x8f7b_72a5,
+            0xed21_633a,
+            0x546c_363d,
+            0x4cf2_72d3,
+            0xe70d_a8e2,
+        ];
+        for len in 0..data.len() + 1 {
+            assert_eq!(leveldb[len], hash(&data[..len], SEED, false));
+            assert_eq!(rocksdb[len], hash(&data[..len], SEED, true));
+        }
+        // from hash_test.cc of LevelDB
+        assert_eq!(0xef13_45c4, hash(&[0x62], SEED, false));
+        assert_eq!(0x5b66_3814, hash(&[0xc3, 0x97], SEED, false));
+        assert_eq!(0x323c_078f, hash(&[0xe2, 0x99, 0xa5], SEED, false));
+    }
+
+    // created by `CreateFilter` of LevelDB 1.22 with 10 bits per key
+    #[test]
+    fn fixture() {
+        let keys: [&[u8]; 3] = [b"hello", b"world", b"\xc3\x97level"];
+        let mut filter = Filter::new(keys.len(), 10);
+        keys.iter().for_each(|key| filter.insert(key));
+        assert_eq!(hex("11c001416410401c06"), filter.as_bytes());
+
+        let filter = Filter::from_bytes(hex("11c001416410401c06"));
+        assert!(keys.iter().all(|key| filter.contains(key)));
+        assert!(!filter.contains(b"bloom") && !filter.contains(b"filter") && !filter.contains(b""));
+        assert_eq!(126, Filter::new(100, 10).as_bytes().len());
+    }
+
+    #[test]
+    fn reserved() {
+        assert!(!Filter::from_bytes(vec![0xff]).contains(b"hello"));
+        let mut filter = Filter::from_bytes(vec![0, 0, 0, 31]);
+        filter.insert(b"hello");
+        assert_eq!(&[0, 0, 0, 31], filter.as_bytes());
+        assert!(filter.contains(b"world"));
+    }
+}
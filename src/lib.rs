@@ -1,23 +1,68 @@
 use std::hash::Hash;
 
+#[cfg(all(feature = "mmap", target_endian = "big"))]
+compile_error!("the mmap feature only supports little-endian targets");
+
+mod bip37;
+mod bit_sliced;
+mod bloomier;
 mod buckets;
+mod cascade;
 mod classic;
 #[cfg(feature = "const_generics")]
 mod const_generics;
+mod count_sketch;
 mod counting;
+pub mod format;
+mod frequency;
+pub mod go;
+mod guava;
 mod hash;
+mod index;
+mod leveldb;
+mod multi_attribute;
+mod orc;
+mod prefix;
+mod range;
+mod rocksdb;
+mod shifting;
+mod spatial;
 mod stable;
+mod theta;
+mod tinylfu;
+mod variable_increment;
+mod weighted;
 
-pub use crate::classic::Filter as ClassicBloomFilter;
+pub use crate::bip37::{Filter as Bip37BloomFilter, Update as Bip37Update};
+pub use crate::bit_sliced::FilterSet as BitSlicedFilterSet;
+pub use crate::bloomier::Filter as BloomierFilter;
+pub use crate::cascade::Filter as BloomFilterCascade;
+pub use crate::classic::{Filter as ClassicBloomFilter, FilterRef as ClassicBloomFilterRef};
 #[cfg(feature = "const_generics")]
 pub use crate::const_generics::{
     buckets::{approximate_bucket_count, compute_word_num},
     classic::Filter as ConstClassicBloomFilter,
     stable::Filter as ConstStableBloomFilter,
 };
+pub use crate::count_sketch::Sketch as CountSketch;
 pub use crate::counting::Filter as CountingBloomFilter;
+pub use crate::frequency::Sketch as FrequencySketch;
+pub use crate::guava::Filter as GuavaBloomFilter;
 pub use crate::hash::{BuildHashKernels, DefaultBuildHashKernels, DefaultBuildHasher, DefaultHashKernels, HashKernels};
+pub use crate::index::Index as BloomFilterIndex;
+pub use crate::leveldb::Filter as LevelDbBloomFilter;
+pub use crate::multi_attribute::Filter as MultiAttributeBloomFilter;
+pub use crate::orc::{Encoding as OrcEncoding, Filter as OrcBloomFilter};
+pub use crate::prefix::{DelimiterPrefix, Filter as PrefixBloomFilter, FixedPrefix, PrefixExtractor};
+pub use crate::range::Filter as RangeBloomFilter;
+pub use crate::rocksdb::Filter as RocksDbBloomFilter;
+pub use crate::shifting::{Association, Filter as ShiftingBloomFilter};
+pub use crate::spatial::Filter as SpatialBloomFilter;
 pub use crate::stable::Filter as StableBloomFilter;
+pub use crate::theta::Sketch as ThetaSketch;
+pub use crate::tinylfu::Policy as TinyLfu;
+pub use crate::variable_increment::Filter as VariableIncrementBloomFilter;
+pub use crate::weighted::Filter as WeightedBloomFilter;
 
 pub trait BloomFilter {
     fn insert<T: Hash>(&mut self, item: &T);
@@ -0,0 +1,142 @@
+use crate::buckets::Buckets;
+use crate::{BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+// max number of fields of a record, fields combinations are stored as bit masks
+const MAX_FIELDS: usize = 64;
+
+/// Multi-attribute bloom filter for records of several fields. Every field and
+/// every selected combination of fields of a record is inserted in its own
+/// hash domain, so keys made of any subset of the fields can be queried.
+pub struct Filter<BHK: BuildHashKernels> {
+    buckets: Buckets,      // filter data
+    hash_kernels: BHK::HK, // hash kernels
+    fields: usize,         // number of fields of a record
+    domains: Vec<u64>,     // masks of the single fields and the selected combinations
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new multi-attribute bloom filter structure.
+    /// items_count is an estimation of the maximum number of records to store.
+    /// fields is the number of fields of a record, in [1, 64]
+    /// combinations are the fields combinations inserted besides the single fields.
+    /// fp_rate is the wanted rate of false positives of every domain, in ]0.0, 1.0[
+    pub fn new(items_count: usize, fields: usize, combinations: &[&[usize]], fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        debug_assert!(fields > 0 && fields <= MAX_FIELDS);
+        let mut domains = (0..fields).map(|field| 1 << field).collect::<Vec<u64>>();
+        for combination in combinations {
+            let mask = combination.iter().fold(0, |mask, field| {
+                debug_assert!(*field < fields);
+                mask | 1 << field
+            });
+            if !domains.contains(&mask) {
+                domains.push(mask);
+            }
+        }
+        // every record inserts each of its domains
+        let buckets = Buckets::with_fp_rate(items_count * domains.len(), fp_rate, 1);
+        let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len());
+        Self {
+            buckets,
+            hash_kernels,
+            fields,
+            domains,
+        }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    /// Inserts a record, `record` holds the value of every field in order.
+    pub fn insert<T: Hash>(&mut self, record: &[T]) {
+        debug_assert_eq!(self.fields, record.len());
+        for mask in self.domains.iter() {
+            let values = record
+                .iter()
+                .enumerate()
+                .filter(|(field, _)| mask & 1 << field != 0)
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            let buckets = &mut self.buckets;
+            self.hash_kernels.hash_iter(&(mask, values)).for_each(|i| buckets.set(i, 1))
+        }
+    }
+
+    /// Returns whether a record matching the partial key may have been
+    /// inserted, `key` holds `(field, value)` pairs of distinct fields. Every
+    /// single field and selected combination covered by the key is checked.
+    pub fn may_contain<T: Hash>(&self, key: &[(usize, T)]) -> bool {
+        let mut key = key.iter().collect::<Vec<_>>();
+        key.sort_by_key(|(field, _)| *field);
+        debug_assert!(key.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let query = key.iter().fold(0u64, |mask, (field, _)| mask | 1 << field);
+        self.domains.iter().filter(|mask| *mask & !query == 0).all(|mask| {
+            let values = key
+                .iter()
+                .filter(|(field, _)| mask & 1 << field != 0)
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            self.hash_kernels.hash_iter(&(mask, values)).all(|i| self.buckets.get(i) == 1)
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _may_contain(records: &[(u64, u64, u64)]) {
+        let mut filter = Filter::new(
+            100,
+            3,
+            &[&[0, 1], &[0, 1, 2]],
+            0.01,
+            DefaultBuildHashKernels::new(random(), RandomState::new()),
+        );
+        records.iter().for_each(|(a, b, c)| filter.insert(&[a, b, c]));
+        assert!(records.iter().all(|(a, b, c)| {
+            filter.may_contain(&[(0, a)])
+                && filter.may_contain(&[(2, c)])
+                && filter.may_contain(&[(1, b), (0, a)])
+                && filter.may_contain(&[(0, a), (2, c)])
+                && filter.may_contain(&[(0, a), (1, b), (2, c)])
+        }));
+    }
+
+    proptest! {
+        #[test]
+        fn may_contain(ref records in any_with::<Vec<(u64, u64, u64)>>(size_range(32).lift())) {
+            _may_contain(records)
+        }
+    }
+
+    #[test]
+    fn combinations() {
+        let mut filter = Filter::new(
+            100,
+            3,
+            &[&[0, 1]],
+            0.001,
+            DefaultBuildHashKernels::new(random(), RandomState::new()),
+        );
+        filter.insert(&["chain-1", "script-a", "lock"]);
+        filter.insert(&["chain-2", "script-b", "type"]);
+        assert!(filter.may_contain::<&str>(&[]));
+        assert!(filter.may_contain(&[(1, "script-a")]));
+        assert!(filter.may_contain(&[(0, "chain-2"), (2, "type")]));
+        // every field is present but the combination was never inserted
+        assert!(!filter.may_contain(&[(0, "chain-1"), (1, "script-b")]));
+        assert!(!filter.may_contain(&[(2, "none")]));
+        filter.reset();
+        assert!(!filter.may_contain(&[(1, "script-a")]));
+    }
+}
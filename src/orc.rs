@@ -0,0 +1,436 @@
+use crate::format::{to_usize, Error};
+use std::f64::consts::LN_2;
+use std::io::{Read, Write};
+
+// seed of the Murmur3 64 bits hash of ORC
+const SEED: u64 = 104_729;
+// max number of words, ORC computes the positions with the i32 count of bits
+const MAX_WORDS: usize = i32::MAX as usize / 64;
+
+/// Encodings of the bits in the protobuf `BloomFilter` message of ORC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// `repeated fixed64 bitset = 2`, the `BLOOM_FILTER` streams
+    Bitset,
+    /// `bytes utf8bitset = 3` of little-endian words, the `BLOOM_FILTER_UTF8` streams
+    Utf8Bitset,
+}
+
+/// Bloom filter of Apache ORC (and Hive), a stripe or row group column index.
+/// The k positions of a value are derived from the lower and upper 32 bits of
+/// its 64 bits hash:
+///
+/// - strings (and binary) hash the UTF-8 bytes with ORC's 64 bits Murmur3,
+///   files written before HIVE-12055 used the JVM default charset
+/// - integers (and dates) hash the `i64` with Thomas Wang's integer hash
+/// - doubles (and floats) hash the bits of the `f64` as an integer
+pub struct Filter {
+    data: Vec<u64>,            // bits, bit i is bit i % 64 of word i / 64
+    num_hash_functions: usize, // number of hash functions
+}
+
+impl Filter {
+    /// Create a new ORC bloom filter structure, sized as the ORC writer does.
+    /// expected_entries is an estimation of the maximum number of values to store.
+    /// fpp is the wanted rate of false positives, in ]0.0, 1.0[, 0.05 by default in ORC
+    pub fn new(expected_entries: usize, fpp: f64) -> Self {
+        debug_assert!(fpp > 0.0 && fpp < 1.0);
+        let n = expected_entries.max(1) as f64;
+        let bits = (-n * fpp.ln() / (LN_2 * LN_2)) as i32 as usize;
+        // ORC always adds bits up to the next multiple of 64
+        let words = (bits / 64 + 1).min(MAX_WORDS);
+        let num_hash_functions = ((words * 64) as f64 / n * LN_2).round().max(1.0) as usize;
+        Self {
+            data: vec![0; words],
+            num_hash_functions,
+        }
+    }
+
+    pub fn data(&self) -> &[u64] {
+        &self.data
+    }
+
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    pub fn insert_bytes(&mut self, value: &[u8]) {
+        self.insert_hash(murmur3_64(value))
+    }
+
+    pub fn insert_long(&mut self, value: i64) {
+        self.insert_hash(long_hash(value))
+    }
+
+    pub fn insert_double(&mut self, value: f64) {
+        self.insert_long(double_to_long_bits(value))
+    }
+
+    pub fn contains_bytes(&self, value: &[u8]) -> bool {
+        self.contains_hash(murmur3_64(value))
+    }
+
+    pub fn contains_long(&self, value: i64) -> bool {
+        self.contains_hash(long_hash(value))
+    }
+
+    pub fn contains_double(&self, value: f64) -> bool {
+        self.contains_long(double_to_long_bits(value))
+    }
+
+    pub fn reset(&mut self) {
+        self.data.iter_mut().for_each(|x| *x = 0)
+    }
+
+    /// Writes the filter as a protobuf `BloomFilter` message.
+    pub fn write_to<W: Write>(&self, mut writer: W, encoding: Encoding) -> Result<(), Error> {
+        writer.write_all(&self.to_message(encoding))?;
+        Ok(())
+    }
+
+    /// Reads a filter from a protobuf `BloomFilter` message, with either
+    /// encoding of the bits.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_message(&bytes)
+    }
+
+    /// Writes the filters of a column as a protobuf `BloomFilterIndex`
+    /// message, the decompressed content of its bloom filter stream.
+    pub fn write_index<W: Write>(filters: &[Self], mut writer: W, encoding: Encoding) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        for filter in filters {
+            let message = filter.to_message(encoding);
+            bytes.push(0x0a);
+            write_varint(&mut bytes, message.len() as u64);
+            bytes.extend_from_slice(&message);
+        }
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Reads the filters of a `BloomFilterIndex` message, one per row group.
+    pub fn read_index<R: Read>(mut reader: R) -> Result<Vec<Self>, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut filters = Vec::new();
+        let mut message = &bytes[..];
+        while !message.is_empty() {
+            match read_field(&mut message)? {
+                (1, Field::Bytes(filter)) => filters.push(Self::from_message(filter)?),
+                (1, _) => return Err(Error::Inconsistent("unexpected wire type")),
+                _ => {}
+            }
+        }
+        Ok(filters)
+    }
+
+    fn to_message(&self, encoding: Encoding) -> Vec<u8> {
+        let mut bytes = vec![0x08];
+        write_varint(&mut bytes, self.num_hash_functions as u64);
+        match encoding {
+            Encoding::Bitset => self.data.iter().for_each(|word| {
+                bytes.push(0x11);
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }),
+            Encoding::Utf8Bitset => {
+                bytes.push(0x1a);
+                write_varint(&mut bytes, self.data.len() as u64 * 8);
+                self.data.iter().for_each(|word| bytes.extend_from_slice(&word.to_le_bytes()));
+            }
+        }
+        bytes
+    }
+
+    fn from_message(mut message: &[u8]) -> Result<Self, Error> {
+        let mut num_hash_functions = 0;
+        let mut data = Vec::new();
+        while !message.is_empty() {
+            match read_field(&mut message)? {
+                (1, Field::Varint(value)) => num_hash_functions = to_usize(value)?,
+                (2, Field::Fixed64(word)) => data.push(word),
+                // packed bitset
+                (2, Field::Bytes(words)) | (3, Field::Bytes(words)) => {
+                    if words.len() % 8 != 0 {
+                        return Err(Error::Inconsistent("bitset length must be a multiple of 8"));
+                    }
+                    data.extend(words.chunks_exact(8).map(|chunk| {
+                        let mut buf = [0; 8];
+                        buf.copy_from_slice(chunk);
+                        u64::from_le_bytes(buf)
+                    }))
+                }
+                (1, _) | (2, _) | (3, _) => return Err(Error::Inconsistent("unexpected wire type")),
+                _ => {}
+            }
+        }
+        if num_hash_functions == 0 || data.is_empty() {
+            return Err(Error::Inconsistent("hash functions and bitset must not be empty"));
+        }
+        if data.len() > MAX_WORDS {
+            return Err(Error::Inconsistent("bitset exceeds i32::MAX bits"));
+        }
+        Ok(Self {
+            data,
+            num_hash_functions,
+        })
+    }
+
+    fn insert_hash(&mut self, hash: u64) {
+        let data = &mut self.data;
+        positions(hash, data.len(), self.num_hash_functions).for_each(|i| data[i / 64] |= 1 << (i % 64))
+    }
+
+    fn contains_hash(&self, hash: u64) -> bool {
+        positions(hash, self.data.len(), self.num_hash_functions).all(|i| self.data[i / 64] & (1 << (i % 64)) != 0)
+    }
+}
+
+// returns the bit positions of hash in a filter of `words` words, with the
+// 32 bits arithmetic of java
+fn positions(hash: u64, words: usize, num_hash_functions: usize) -> impl Iterator<Item = usize> {
+    let bits = (words * 64) as i32;
+    let (hash1, hash2) = (hash as i32, (hash >> 32) as i32);
+    (1..=num_hash_functions as i32).map(move |i| {
+        let combined = hash1.wrapping_add(i.wrapping_mul(hash2));
+        // flip all the bits of negative hashes
+        let combined = if combined < 0 { !combined } else { combined };
+        (combined % bits) as usize
+    })
+}
+
+// Thomas Wang's 64 bits integer hash
+fn long_hash(key: i64) -> u64 {
+    let key = (!key).wrapping_add(key << 21);
+    let key = key ^ (key >> 24);
+    let key = key.wrapping_add(key << 3).wrapping_add(key << 8);
+    let key = key ^ (key >> 14);
+    let key = key.wrapping_add(key << 2).wrapping_add(key << 4);
+    let key = key ^ (key >> 28);
+    key.wrapping_add(key << 31) as u64
+}
+
+// `Double.doubleToLongBits`, which collapses all the NaN to the canonical one
+fn double_to_long_bits(value: f64) -> i64 {
+    if value.is_nan() {
+        0x7ff8_0000_0000_0000
+    } else {
+        value.to_bits() as i64
+    }
+}
+
+// Murmur3 64 bits of ORC, a single lane variant of MurmurHash3 x64 128 bits
+fn murmur3_64(data: &[u8]) -> u64 {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    let mix = |k: u64| k.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    let read = |bytes: &[u8]| bytes.iter().rev().fold(0, |k, byte| k << 8 | u64::from(*byte));
+
+    let mut chunks = data.chunks_exact(8);
+    let mut hash = chunks.by_ref().fold(SEED, |hash, chunk| {
+        (hash ^ mix(read(chunk)))
+            .rotate_left(27)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729)
+    });
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        hash ^= mix(read(tail));
+    }
+
+    hash ^= data.len() as u64;
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32,
+}
+
+// reads a protobuf field, returns its number and value
+fn read_field<'a>(message: &mut &'a [u8]) -> Result<(u64, Field<'a>), Error> {
+    let key = read_varint(message)?;
+    let field = match key & 7 {
+        0 => Field::Varint(read_varint(message)?),
+        1 => {
+            let bytes = take(message, 8)?;
+            Field::Fixed64(bytes.iter().rev().fold(0, |k, byte| k << 8 | u64::from(*byte)))
+        }
+        2 => {
+            let len = to_usize(read_varint(message)?)?;
+            Field::Bytes(take(message, len)?)
+        }
+        5 => {
+            take(message, 4)?;
+            Field::Fixed32
+        }
+        _ => return Err(Error::Inconsistent("unsupported wire type")),
+    };
+    Ok((key >> 3, field))
+}
+
+fn take<'a>(message: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if message.len() < len {
+        return Err(Error::Inconsistent("truncated message"));
+    }
+    let (bytes, rest) = message.split_at(len);
+    *message = rest;
+    Ok(bytes)
+}
+
+fn read_varint(message: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = take(message, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Inconsistent("varint overflows u64"))
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len() / 2)
+            .map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn _contains(values: &[(Vec<u8>, i64, f64)]) {
+        let mut filter = Filter::new(values.len() * 3, 0.05);
+        for (bytes, long, double) in values {
+            filter.insert_bytes(bytes);
+            filter.insert_long(*long);
+            filter.insert_double(*double);
+        }
+        assert!(values.iter().all(|(bytes, long, double)| {
+            filter.contains_bytes(bytes) && filter.contains_long(*long) && filter.contains_double(*double)
+        }));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref values in any_with::<Vec<(Vec<u8>, i64, f64)>>(size_range(16).lift())) {
+            _contains(values)
+        }
+    }
+
+    // `Murmur3.hash64` of the prefixes of the bytes `i * 7 + 3` and `getLongHash`
+    #[test]
+    fn hash_vectors() {
+        let input = (0..40).map(|i| (i * 7 + 3) as u8).collect::<Vec<_>>();
+        let vectors = [
+            (0, 0x74a1_8dc8_f20a_db48),
+            (1, 0x22d8_08f6_cea7_21b6),
+            (7, 0xfa59_9531_7a48_d5ac),
+            (8, 0x2548_9869_b457_0109),
+            (9, 0x90df_79ff_1339_5606),
+            (16, 0x2274_ac19_3e1b_3de6),
+            (23, 0xb087_a79b_ada6_d804),
+            (40, 0x3b23_85e3_c984_47cc),
+        ];
+        for (len, expected) in vectors.iter() {
+            assert_eq!(*expected, murmur3_64(&input[..*len]));
+        }
+        let vectors = [
+            (0, 0),
+            (1, 0x5bca_7c69_b794_f8ce),
+            (-1, 0x5bca_8684_3795_0d03),
+            (i64::MIN, 0x3be7_d0f7_780d_e548),
+            (1_234_567_890_123, 0x2331_ba7f_2269_f7c6),
+        ];
+        for (key, expected) in vectors.iter() {
+            assert_eq!(*expected, long_hash(*key));
+        }
+    }
+
+    // the message of a filter of 64 bits and 4 hash functions
+    const BITSET: &str = "08041113091885b4e0fc52";
+    const UTF8_BITSET: &str = "08041a0813091885b4e0fc52";
+
+    #[test]
+    fn fixture() {
+        let mut filter = Filter::new(10, 0.05);
+        ["hello", "world", "ünïcödé"]
+            .iter()
+            .for_each(|s| filter.insert_bytes(s.as_bytes()));
+        [-7, 0, 1 << 40].iter().for_each(|v| filter.insert_long(*v));
+        [-0.5, 3.25, f64::NAN].iter().for_each(|v| filter.insert_double(*v));
+        assert_eq!(&[0x52fc_e0b4_8518_0913], filter.data());
+
+        for (encoding, expected) in [(Encoding::Bitset, BITSET), (Encoding::Utf8Bitset, UTF8_BITSET)].iter() {
+            let mut bytes = Vec::new();
+            filter.write_to(&mut bytes, *encoding).unwrap();
+            assert_eq!(hex(expected), bytes);
+
+            let filter = Filter::read_from(&bytes[..]).unwrap();
+            assert_eq!(4, filter.num_hash_functions());
+            assert!(["hello", "world", "ünïcödé"]
+                .iter()
+                .all(|s| filter.contains_bytes(s.as_bytes())));
+            assert!(["bloom", "filter", ""].iter().all(|s| !filter.contains_bytes(s.as_bytes())));
+            assert!([-7, 0, 1 << 40].iter().all(|v| filter.contains_long(*v)));
+            assert!([2, 3, -8].iter().all(|v| !filter.contains_long(*v)));
+            // all the NaN are the same value
+            assert!(filter.contains_double(-0.5) && filter.contains_double(3.25));
+            assert!(filter.contains_double(f64::from_bits(0x7ff0_0000_0000_0001)));
+            assert!(!filter.contains_double(0.5) && !filter.contains_double(1.0));
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let sizes = [(10_000, 0.05, 4, 975), (1, 0.5, 44, 1), (0, 0.01, 44, 1), (64, 0.1, 3, 5)];
+        for (n, fpp, k, words) in sizes.iter() {
+            let filter = Filter::new(*n, *fpp);
+            assert_eq!((*k, *words), (filter.num_hash_functions(), filter.data().len()));
+        }
+        // the largest bitset of which the bits fit in an i32
+        assert!(MAX_WORDS * 64 <= i32::MAX as usize && (MAX_WORDS + 1) * 64 > i32::MAX as usize);
+    }
+
+    #[test]
+    fn index() {
+        let mut filters = vec![Filter::new(10, 0.05), Filter::new(100, 0.01)];
+        filters[0].insert_long(42);
+        filters[1].insert_bytes(b"orc");
+        let mut bytes = Vec::new();
+        Filter::write_index(&filters, &mut bytes, Encoding::Utf8Bitset).unwrap();
+        assert_eq!(hex("0a0c"), &bytes[..2]);
+
+        let filters = Filter::read_index(&bytes[..]).unwrap();
+        assert_eq!(2, filters.len());
+        assert!(filters[0].contains_long(42) && filters[1].contains_bytes(b"orc"));
+        assert!(Filter::read_index(&[][..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_messages() {
+        // packed bitset and an unknown field
+        let filter = Filter::read_from(&hex("0804120813091885b4e0fc522001")[..]).unwrap();
+        assert_eq!(&[0x52fc_e0b4_8518_0913], filter.data());
+
+        for message in ["0804", "1a0813091885b4e0fc52", "08041a0713091885b4e0fc", "080411130918"].iter() {
+            assert!(matches!(Filter::read_from(&hex(message)[..]), Err(Error::Inconsistent(_))));
+        }
+        assert!(matches!(Filter::read_from(&hex("0f04")[..]), Err(Error::Inconsistent(_))));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::buckets::Buckets;
+use crate::{BuildHashKernels, HashKernels};
+
+// whole keys and prefixes are hashed in different domains
+const KEY_DOMAIN: u8 = 0;
+const PREFIX_DOMAIN: u8 = 1;
+
+/// A trait for extracting the prefix of a key.
+pub trait PrefixExtractor {
+    /// Returns `None` if the key is out of the extractor's domain.
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]>;
+}
+
+/// Extracts the first `len` bytes of keys, keys shorter than `len` have no prefix.
+pub struct FixedPrefix(pub usize);
+
+impl PrefixExtractor for FixedPrefix {
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.get(..self.0)
+    }
+}
+
+/// Extracts the bytes before the first delimiter, keys without delimiter have no prefix.
+pub struct DelimiterPrefix(pub u8);
+
+impl PrefixExtractor for DelimiterPrefix {
+    fn prefix<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.iter().position(|b| *b == self.0).map(|i| &key[..i])
+    }
+}
+
+pub struct Filter<BHK: BuildHashKernels, PE: PrefixExtractor> {
+    buckets: Buckets,      // filter data
+    hash_kernels: BHK::HK, // hash kernels
+    extractor: PE,         // prefix extractor
+}
+
+impl<BHK: BuildHashKernels, PE: PrefixExtractor> Filter<BHK, PE> {
+    /// Create a new prefix bloom filter structure.
+    /// items_count is an estimation of the maximum number of keys to store.
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(items_count: usize, fp_rate: f64, extractor: PE, build_hash_kernels: BHK) -> Self {
+        // every key inserts itself and its prefix
+        let buckets = Buckets::with_fp_rate(items_count * 2, fp_rate, 1);
+        let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len());
+        Self {
+            buckets,
+            hash_kernels,
+            extractor,
+        }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        self.set(KEY_DOMAIN, key);
+        if let Some(prefix) = self.extractor.prefix(key) {
+            self.set(PREFIX_DOMAIN, prefix);
+        }
+    }
+
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.get(KEY_DOMAIN, key)
+    }
+
+    /// Returns whether any key with the given prefix may have been inserted,
+    /// `prefix` must be a prefix produced by the extractor.
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        self.get(PREFIX_DOMAIN, prefix)
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.reset()
+    }
+
+    fn set(&mut self, domain: u8, bytes: &[u8]) {
+        let buckets = &mut self.buckets;
+        self.hash_kernels.hash_iter(&(domain, bytes)).for_each(|i| buckets.set(i, 1))
+    }
+
+    fn get(&self, domain: u8, bytes: &[u8]) -> bool {
+        self.hash_kernels
+            .hash_iter(&(domain, bytes))
+            .all(|i| self.buckets.get(i) == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _contains(keys: &[Vec<u8>]) {
+        let mut filter = Filter::new(
+            100,
+            0.01,
+            FixedPrefix(4),
+            DefaultBuildHashKernels::new(random(), RandomState::new()),
+        );
+        keys.iter().for_each(|key| filter.insert(key));
+        assert!(keys.iter().all(|key| filter.contains(key)));
+        assert!(keys
+            .iter()
+            .filter(|key| key.len() >= 4)
+            .all(|key| filter.may_contain_prefix(&key[..4])));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref keys in any_with::<Vec<Vec<u8>>>(size_range(16).lift())) {
+            _contains(keys)
+        }
+    }
+
+    #[test]
+    fn extractors() {
+        assert_eq!(Some(&b"ab"[..]), FixedPrefix(2).prefix(b"abc"));
+        assert_eq!(None, FixedPrefix(4).prefix(b"abc"));
+        assert_eq!(Some(&b"user"[..]), DelimiterPrefix(b':').prefix(b"user:42"));
+        assert_eq!(None, DelimiterPrefix(b':').prefix(b"user42"));
+    }
+
+    #[test]
+    fn may_contain_prefix() {
+        let mut filter = Filter::new(
+            100,
+            0.01,
+            DelimiterPrefix(b':'),
+            DefaultBuildHashKernels::new(random(), RandomState::new()),
+        );
+        filter.insert(b"user:42");
+        filter.insert(b"order:7");
+        assert!(filter.may_contain_prefix(b"user"));
+        assert!(filter.may_contain_prefix(b"order"));
+        assert!(filter.contains(b"user:42"));
+    }
+}
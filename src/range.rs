@@ -0,0 +1,122 @@
+use crate::classic::Filter as ClassicFilter;
+use crate::{BloomFilter, BuildHashKernels};
+
+/// A range filter over u64 keys built on dyadic prefixes: every key is stored
+/// at each level l as the prefix `key >> l`, so a range query is answered by
+/// probing the dyadic intervals covering it, then refining each positive
+/// interval down to level 0.
+pub struct Filter<BHK: BuildHashKernels> {
+    filter: ClassicFilter<BHK>, // prefixes of all levels
+    max_level: u32,             // prefixes are stored for levels 0..=max_level
+    max_range: u64,             // maximum width of range queries
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new range filter structure.
+    /// items_count is an estimation of the maximum number of keys to store.
+    /// max_range is the maximum width of the ranges passed to `may_contain_range`.
+    /// fp_rate is the wanted rate of false positives of a range query, in ]0.0, 1.0[
+    pub fn new(items_count: usize, max_range: u64, fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        debug_assert!(max_range > 0);
+        let max_level = (64 - max_range.saturating_sub(1).leading_zeros()).min(63);
+        let levels = max_level as usize + 1;
+        // a range of width up to 2 ^ max_level is covered by at most 2 * levels dyadic intervals
+        let filter = ClassicFilter::new(items_count * levels, fp_rate / (2 * levels) as f64, build_hash_kernels);
+        Self {
+            filter,
+            max_level,
+            max_range,
+        }
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        for level in 0..=self.max_level {
+            self.filter.insert(&(level, key >> level))
+        }
+    }
+
+    pub fn contains_point(&self, key: u64) -> bool {
+        self.filter.contains(&(0u32, key))
+    }
+
+    /// Returns whether any key in [lo, hi] may have been inserted. Ranges wider
+    /// than `max_range` can not be answered and always return `true`.
+    pub fn may_contain_range(&self, lo: u64, hi: u64) -> bool {
+        if lo > hi {
+            return false;
+        }
+        if hi - lo >= self.max_range {
+            return true;
+        }
+
+        let mut lo = lo;
+        loop {
+            // the largest dyadic interval starting at lo and ending before hi
+            let mut level = lo.trailing_zeros().min(self.max_level);
+            while level > 0 && hi - lo < (1 << level) - 1 {
+                level -= 1;
+            }
+            if self.probe(lo >> level, level) {
+                return true;
+            }
+            let end = lo + ((1 << level) - 1);
+            if end >= hi {
+                return false;
+            }
+            lo = end + 1;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset()
+    }
+
+    fn probe(&self, prefix: u64, level: u32) -> bool {
+        self.filter.contains(&(level, prefix))
+            && (level == 0 || self.probe(prefix << 1, level - 1) || self.probe((prefix << 1) | 1, level - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _contains(keys: &[u64]) {
+        let mut filter = Filter::new(100, 1024, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        keys.iter().for_each(|key| filter.insert(*key));
+        assert!(keys.iter().all(|key| filter.contains_point(*key)));
+        assert!(keys.iter().all(|key| filter.may_contain_range(*key, *key)));
+        assert!(keys
+            .iter()
+            .all(|key| filter.may_contain_range(key.saturating_sub(500), key.saturating_add(500))));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref keys in any_with::<Vec<u64>>(size_range(16).lift())) {
+            _contains(keys)
+        }
+    }
+
+    #[test]
+    fn may_contain_range() {
+        let mut filter = Filter::new(100, 1 << 16, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        filter.insert(1000);
+        filter.insert(u64::MAX);
+        assert!(filter.may_contain_range(0, 1000));
+        assert!(filter.may_contain_range(1000, 2000));
+        assert!(filter.may_contain_range(u64::MAX - 10, u64::MAX));
+        assert!(!filter.may_contain_range(2000, 1000));
+        // wider than max_range
+        assert!(filter.may_contain_range(0, 1 << 20));
+
+        let false_positives = (0..1000u64)
+            .filter(|i| filter.may_contain_range(10_000 + i * 100, 10_000 + i * 100 + 99))
+            .count();
+        assert!(false_positives < 50);
+    }
+}
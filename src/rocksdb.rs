@@ -0,0 +1,444 @@
+use crate::leveldb::hash;
+use std::convert::TryInto;
+
+// seed of the legacy hash of keys
+const SEED: u32 = 0xbc9f_1d34;
+// length of the metadata trailer of filter blocks
+const METADATA_LEN: usize = 5;
+// cache line size of the legacy filters written by this crate
+const LOG2_CACHE_LINE_BYTES: u32 = 6;
+// multiplier of the probes of FastLocalBloom
+const PROBE_MULTIPLIER: u32 = 0x9e37_79b9;
+
+// format of the filter block, from its metadata
+enum Kind {
+    AlwaysFalse, // block without metadata, no key was added
+    AlwaysTrue,  // reserved or unsupported formats, such as Ribbon filters
+    Legacy {
+        num_probes: u8,
+        num_lines: u32,
+        log2_cache_line_bytes: u32,
+    },
+    FastLocal {
+        num_probes: u8,
+    },
+}
+
+/// Full filter block of the built-in `rocksdb.BuiltinBloomFilter` policy of
+/// RocksDB, the bits followed by a 5 bytes metadata trailer:
+///
+/// | format                                 | trailer                                    |
+/// |----------------------------------------|--------------------------------------------|
+/// | legacy (`format_version` < 5)          | num_probes, `u32` LE number of cache lines |
+/// | FastLocalBloom (`format_version` >= 5) | -1, 0, num_probes, 0, 0                    |
+///
+/// Legacy filters hash keys with the 32 bits hash of LevelDB and probe a
+/// single cache line with double hashing. FastLocalBloom filters hash keys
+/// with the XXH3 preview of RocksDB and probe a 64 bytes cache line chosen by
+/// the lower 32 bits of the hash with the upper 32 bits.
+pub struct Filter {
+    data: Vec<u8>, // filter block
+    kind: Kind,    // format of the block
+}
+
+impl Filter {
+    /// Create a new legacy filter block with 64 bytes cache lines.
+    /// keys_count is the number of keys of the block, at least 1
+    /// bits_per_key is the parameter of the policy, usually 10
+    pub fn legacy(keys_count: usize, bits_per_key: usize) -> Self {
+        debug_assert!(keys_count > 0 && bits_per_key > 0);
+        // round down to reduce probing cost, as RocksDB does
+        let num_probes = ((bits_per_key as f64 * 0.69) as u8).clamp(1, 30);
+        // total bits cannot exceed 2^32 for compatibility
+        let bits = (keys_count * bits_per_key).min(0xffff_0000) as u32;
+        let line_bits = 8 << LOG2_CACHE_LINE_BYTES;
+        // an odd number of lines involves more bits when choosing a line
+        let num_lines = ((bits + line_bits - 1) / line_bits) | 1;
+        let len = (num_lines << LOG2_CACHE_LINE_BYTES) as usize;
+        let mut data = vec![0; len + METADATA_LEN];
+        data[len] = num_probes;
+        data[len + 1..].copy_from_slice(&num_lines.to_le_bytes());
+        Self {
+            data,
+            kind: Kind::Legacy {
+                num_probes,
+                num_lines,
+                log2_cache_line_bytes: LOG2_CACHE_LINE_BYTES,
+            },
+        }
+    }
+
+    /// Create a new FastLocalBloom filter block, without the rounding of
+    /// `optimize_filters_for_memory`.
+    /// keys_count is the number of keys of the block, at least 1
+    /// bits_per_key is the parameter of the policy, in [1.0, 100.0[
+    pub fn fast_local(keys_count: usize, bits_per_key: f64) -> Self {
+        debug_assert!(keys_count > 0);
+        debug_assert!((1.0..100.0).contains(&bits_per_key));
+        let millibits_per_key = (bits_per_key * 1000.0 + 0.500_001) as usize;
+        let len = ((keys_count * millibits_per_key + 7999) / 8000).min(0xffff_ffc0);
+        // round up to a multiple of the 64 bytes cache lines
+        let len = (len + 63) & !63;
+        let num_probes = choose_num_probes(millibits_per_key);
+        let mut data = vec![0; len + METADATA_LEN];
+        data[len] = 0xff;
+        data[len + 2] = num_probes;
+        Self {
+            data,
+            kind: Kind::FastLocal { num_probes },
+        }
+    }
+
+    /// Uses a filter block read from a table file, its format is read from the
+    /// metadata. Blocks of reserved or unsupported formats, such as Ribbon
+    /// filters, match every key as RocksDB does with unknown formats.
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        let kind = if data.len() <= METADATA_LEN {
+            Kind::AlwaysFalse
+        } else {
+            let len = data.len() - METADATA_LEN;
+            match data[len] as i8 {
+                -1 => fast_local_kind(&data[len..]),
+                num_probes if num_probes > 0 => {
+                    let num_lines = u32::from_le_bytes(data[len + 1..].try_into().unwrap());
+                    legacy_kind(num_probes as u8, num_lines, len as u64)
+                }
+                // 0 probes, Ribbon filters (-2) and reserved markers
+                _ => Kind::AlwaysTrue,
+            }
+        };
+        Self { data, kind }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Inserts a key, blocks which always or never match ignore the inserts.
+    pub fn insert(&mut self, key: &[u8]) {
+        let (offset, positions) = match self.probes(key) {
+            Some(probes) => probes,
+            None => return,
+        };
+        for i in positions {
+            self.data[offset + i / 8] |= 1 << (i % 8);
+        }
+    }
+
+    /// Returns whether the key may be in the block, as `MayMatch` does.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        match self.kind {
+            Kind::AlwaysFalse => false,
+            Kind::AlwaysTrue => true,
+            _ => {
+                let (offset, mut positions) = self.probes(key).unwrap();
+                positions.all(|i| self.data[offset + i / 8] & (1 << (i % 8)) != 0)
+            }
+        }
+    }
+
+    // returns the offset of the cache line of key and the bit positions in it
+    fn probes(&self, key: &[u8]) -> Option<(usize, Box<dyn Iterator<Item = usize>>)> {
+        match self.kind {
+            Kind::Legacy {
+                num_probes,
+                num_lines,
+                log2_cache_line_bytes,
+            } => {
+                let mut h = hash(key, SEED, true);
+                let offset = ((h % num_lines) as usize) << log2_cache_line_bytes;
+                let mask = (1 << (log2_cache_line_bytes + 3)) - 1;
+                let delta = h.rotate_right(17);
+                let positions = (0..num_probes).map(move |_| {
+                    let position = (h & mask) as usize;
+                    h = h.wrapping_add(delta);
+                    position
+                });
+                Some((offset, Box::new(positions)))
+            }
+            Kind::FastLocal { num_probes } => {
+                let h = xxph3(key);
+                let lines = ((self.data.len() - METADATA_LEN) >> 6) as u64;
+                let offset = (((h & 0xffff_ffff) * lines) >> 32 << 6) as usize;
+                let mut h = (h >> 32) as u32;
+                let positions = (0..num_probes).map(move |_| {
+                    let position = (h >> 23) as usize;
+                    h = h.wrapping_mul(PROBE_MULTIPLIER);
+                    position
+                });
+                Some((offset, Box::new(positions)))
+            }
+            Kind::AlwaysFalse | Kind::AlwaysTrue => None,
+        }
+    }
+}
+
+fn legacy_kind(num_probes: u8, num_lines: u32, len: u64) -> Kind {
+    let num_lines_64 = u64::from(num_lines);
+    if num_lines == 0 || len % num_lines_64 != 0 {
+        return Kind::AlwaysTrue;
+    }
+    // the cache line size of the writer must be a power of two, its bit positions fit in 32 bits
+    let log2_cache_line_bytes = (len / num_lines_64).trailing_zeros();
+    if num_lines_64 << log2_cache_line_bytes != len || log2_cache_line_bytes > 28 {
+        return Kind::AlwaysTrue;
+    }
+    Kind::Legacy {
+        num_probes,
+        num_lines,
+        log2_cache_line_bytes,
+    }
+}
+
+fn fast_local_kind(metadata: &[u8]) -> Kind {
+    let num_probes = metadata[2] & 0x1f;
+    // only 64 bytes cache lines are supported, the last bytes are reserved
+    if metadata[1] != 0 || metadata[2] >> 5 != 0 || metadata[3..] != [0, 0] || num_probes == 0 || num_probes > 30 {
+        return Kind::AlwaysTrue;
+    }
+    Kind::FastLocal { num_probes }
+}
+
+// FastLocalBloomImpl::ChooseNumProbes
+fn choose_num_probes(millibits_per_key: usize) -> u8 {
+    let thresholds = [2080, 3580, 5100, 6640, 8300, 10070, 11720, 14001, 16050, 18300, 22001, 25501];
+    match thresholds.iter().position(|threshold| millibits_per_key <= *threshold) {
+        Some(i) => i as u8 + 1,
+        None if millibits_per_key > 50000 => 24,
+        None => ((millibits_per_key - 1) / 2000 - 1) as u8,
+    }
+}
+
+const PRIME32_1: u64 = 0x9E37_79B1;
+const PRIME32_2: u64 = 0x85EB_CA77;
+const PRIME32_3: u64 = 0xC2B2_AE3D;
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c, 0xde, 0xd4, 0x6d, 0xe9,
+    0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f, 0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78,
+    0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21, 0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6,
+    0x81, 0x3a, 0x26, 0x4c, 0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8, 0xa8, 0xfa, 0x76, 0x3f,
+    0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d, 0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31,
+    0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64, 0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff,
+    0xfa, 0x13, 0x63, 0xeb, 0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce, 0x45, 0xcb, 0x3a, 0x8f,
+    0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+fn read32(bytes: &[u8]) -> u64 {
+    u64::from(u32::from_le_bytes(bytes[..4].try_into().unwrap()))
+}
+
+fn read64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn mul128_fold64(lhs: u64, rhs: u64) -> u64 {
+    let product = u128::from(lhs) * u128::from(rhs);
+    product as u64 ^ (product >> 64) as u64
+}
+
+fn avalanche(h: u64) -> u64 {
+    let h = (h ^ (h >> 37)).wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+fn mix16(input: &[u8], secret: &[u8]) -> u64 {
+    mul128_fold64(read64(input) ^ read64(secret), read64(&input[8..]) ^ read64(&secret[8..]))
+}
+
+// 64 bits XXH3 preview (0.7.2) without seed, the `Hash64` of RocksDB which
+// hashes the empty key to a non zero value
+fn xxph3(input: &[u8]) -> u64 {
+    let len = input.len();
+    let len_64 = len as u64;
+    match len {
+        0 => mul128_fold64(read64(&SECRET), PRIME64_2),
+        1..=3 => {
+            let combined =
+                u64::from(input[0]) | u64::from(input[len >> 1]) << 8 | u64::from(input[len - 1]) << 16 | len_64 << 24;
+            avalanche((combined ^ read32(&SECRET)).wrapping_mul(PRIME64_1))
+        }
+        4..=8 => {
+            let input_64 = read32(input) | read32(&input[len - 4..]) << 32;
+            let keyed = input_64 ^ read64(&SECRET);
+            let mix = len_64.wrapping_add((keyed ^ (keyed >> 51)).wrapping_mul(PRIME32_1));
+            avalanche((mix ^ (mix >> 47)).wrapping_mul(PRIME64_2))
+        }
+        9..=16 => {
+            let lo = read64(input) ^ read64(&SECRET);
+            let hi = read64(&input[len - 8..]) ^ read64(&SECRET[8..]);
+            avalanche(len_64.wrapping_add(lo.wrapping_add(hi)).wrapping_add(mul128_fold64(lo, hi)))
+        }
+        17..=128 => {
+            let rounds = (len - 1) / 32 + 1;
+            let acc = (0..rounds).fold(len_64.wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &SECRET[32 * i..]))
+                    .wrapping_add(mix16(&input[len - 16 * (i + 1)..], &SECRET[32 * i + 16..]))
+            });
+            avalanche(acc)
+        }
+        129..=240 => {
+            let acc = (0..8).fold(len_64.wrapping_mul(PRIME64_1), |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &SECRET[16 * i..]))
+            });
+            let acc = (8..len / 16).fold(avalanche(acc), |acc, i| {
+                acc.wrapping_add(mix16(&input[16 * i..], &SECRET[16 * (i - 8) + 3..]))
+            });
+            avalanche(acc.wrapping_add(mix16(&input[len - 16..], &SECRET[136 - 17..])))
+        }
+        _ => xxph3_long(input),
+    }
+}
+
+fn xxph3_long(input: &[u8]) -> u64 {
+    let accumulate = |acc: &mut [u64; 8], stripe: &[u8], secret: &[u8]| {
+        for (i, acc) in acc.iter_mut().enumerate() {
+            let value = read64(&stripe[8 * i..]);
+            let key = value ^ read64(&secret[8 * i..]);
+            *acc = acc.wrapping_add(value).wrapping_add((key & 0xffff_ffff) * (key >> 32));
+        }
+    };
+    let len = input.len();
+    let stripes_per_block = (SECRET.len() - 64) / 8;
+    let block_len = 64 * stripes_per_block;
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+    for block in input.chunks(block_len) {
+        for (n, stripe) in block.chunks_exact(64).enumerate() {
+            accumulate(&mut acc, stripe, &SECRET[8 * n..]);
+        }
+        if block.len() == block_len {
+            // scramble
+            for (i, acc) in acc.iter_mut().enumerate() {
+                let key = read64(&SECRET[SECRET.len() - 64 + 8 * i..]);
+                *acc = ((*acc ^ (*acc >> 47)) ^ key).wrapping_mul(PRIME32_1);
+            }
+        }
+    }
+    if len % 64 != 0 {
+        accumulate(&mut acc, &input[len - 64..], &SECRET[SECRET.len() - 64 - 7..]);
+    }
+    let result = (0..4).fold((len as u64).wrapping_mul(PRIME64_1), |result, i| {
+        let secret = &SECRET[11 + 16 * i..];
+        result.wrapping_add(mul128_fold64(
+            acc[2 * i] ^ read64(secret),
+            acc[2 * i + 1] ^ read64(&secret[8..]),
+        ))
+    });
+    avalanche(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len() / 2)
+            .map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap())
+            .collect()
+    }
+
+    const KEYS: [&[u8]; 3] = [b"hello", b"world", b"\xc3\x97rocks"];
+
+    // created with the builders of RocksDB 10.4 with 10 bits per key
+    const LEGACY: &str = "000000010000000000400040000000001000000000000000000000200000801000000200040800002100008000004000000000000010000001000000440000000601000000";
+    const FAST_LOCAL: &str = "00000001004140002000000002000002000210008000000000000000000000000000000000000000020800000004400800000000000040000000080000000000ff00060000";
+
+    fn _contains(items: &[Vec<u8>]) {
+        let count = items.len().max(1);
+        let mut legacy = Filter::legacy(count, 10);
+        let mut fast_local = Filter::fast_local(count, 10.0);
+        items.iter().for_each(|i| legacy.insert(i));
+        items.iter().for_each(|i| fast_local.insert(i));
+        assert!(items.iter().all(|i| legacy.contains(i) && fast_local.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<Vec<u8>>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    // `Hash64` of RocksDB 10.4 of the prefixes of the bytes `i * 7 + 3`
+    #[test]
+    fn xxph3_vectors() {
+        let input = (0..1000).map(|i| (i * 7 + 3) as u8).collect::<Vec<_>>();
+        let vectors = [
+            (0, 0x5342_c301_0fe1_dd04),
+            (1, 0x81b7_8b21_c2ce_4f18),
+            (3, 0x6731_a716_813e_e5f1),
+            (4, 0x3a5a_fd96_7aca_f5c3),
+            (8, 0xbcb5_6b6f_8d4b_12da),
+            (9, 0x0378_7b4e_ec57_bf4b),
+            (16, 0x8172_0cc0_702e_dd73),
+            (17, 0x0b51_5520_f462_e96f),
+            (100, 0x9405_1957_d141_ed9f),
+            (128, 0x8ea7_6d83_8ce7_563f),
+            (129, 0x3992_d120_c8c4_677a),
+            (240, 0xdf41_bc5f_ea3a_61e8),
+            (241, 0x91d1_0f06_82af_4ac3),
+            (1000, 0xcc43_607b_fb57_6d0c),
+        ];
+        for (len, expected) in vectors.iter() {
+            assert_eq!(*expected, xxph3(&input[..*len]), "len {}", len);
+        }
+    }
+
+    #[test]
+    fn legacy_fixture() {
+        let mut filter = Filter::legacy(KEYS.len(), 10);
+        KEYS.iter().for_each(|key| filter.insert(key));
+        assert_eq!(hex(LEGACY), filter.as_bytes());
+
+        let filter = Filter::from_bytes(hex(LEGACY));
+        assert!(KEYS.iter().all(|key| filter.contains(key)));
+        assert!(!filter.contains(b"bloom") && !filter.contains(b"filter") && !filter.contains(b""));
+    }
+
+    #[test]
+    fn fast_local_fixture() {
+        let mut filter = Filter::fast_local(KEYS.len(), 10.0);
+        KEYS.iter().for_each(|key| filter.insert(key));
+        assert_eq!(hex(FAST_LOCAL), filter.as_bytes());
+
+        let filter = Filter::from_bytes(hex(FAST_LOCAL));
+        assert!(KEYS.iter().all(|key| filter.contains(key)));
+        assert!(!filter.contains(b"bloom") && !filter.contains(b"filter") && !filter.contains(b""));
+    }
+
+    #[test]
+    fn metadata() {
+        assert!(!Filter::from_bytes(vec![]).contains(b"hello"));
+        assert!(!Filter::from_bytes(vec![0; 5]).contains(b"hello"));
+        // zero probes, Ribbon and reserved fields match every key
+        for trailer in [[0, 1, 0, 0, 0], [0xfe, 0, 0, 2, 0], [0xff, 0, 0x26, 0, 0], [0xff, 0, 6, 1, 0]].iter() {
+            let mut data = vec![0; 64];
+            data.extend_from_slice(trailer);
+            assert!(Filter::from_bytes(data).contains(b"hello"));
+        }
+
+        // legacy filters of 128 bytes cache lines written on another platform
+        let mut filter = Filter::from_bytes([&[0; 384][..], &[6, 3, 0, 0, 0]].concat());
+        filter.insert(b"hello");
+        assert!(filter.contains(b"hello"));
+        assert!(matches!(
+            filter.kind,
+            Kind::Legacy {
+                log2_cache_line_bytes: 7,
+                ..
+            }
+        ));
+        // the length isn't a power of two multiple of the number of lines
+        assert!(Filter::from_bytes([&[0; 192][..], &[6, 2, 0, 0, 0]].concat()).contains(b"hello"));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::buckets::{compute_m_num, Buckets};
+use crate::hash::compute_k_num;
+use crate::{BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+// maximum offset of set B positions, offsets are in [1, MAX_OFFSET]
+const MAX_OFFSET: usize = 57;
+
+/// The sets an item is associated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Association {
+    A,
+    B,
+    Both,
+    None,
+}
+
+/// Shifting Bloom filter for association queries: items of set A set the
+/// buckets at their k positions, items of set B set the buckets at their k
+/// positions shifted by an offset derived from the same hash, so both sets
+/// are queried with one set of hash computations.
+pub struct Filter<BHK: BuildHashKernels> {
+    buckets: Buckets,      // filter data, MAX_OFFSET buckets larger than positions range
+    hash_kernels: BHK::HK, // k positions followed by the offset
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new shifting bloom filter structure.
+    /// items_count is an estimation of the maximum number of items of both sets.
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(items_count: usize, fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        let m = compute_m_num(items_count, fp_rate);
+        let buckets = Buckets::new(m + MAX_OFFSET, 1);
+        let hash_kernels = build_hash_kernels.with_k(compute_k_num(fp_rate) + 1, m);
+        Self { buckets, hash_kernels }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    pub fn insert_a<T: Hash>(&mut self, item: &T) {
+        let (positions, _) = self.positions(item);
+        positions.into_iter().for_each(|i| self.buckets.set(i, 1))
+    }
+
+    pub fn insert_b<T: Hash>(&mut self, item: &T) {
+        let (positions, offset) = self.positions(item);
+        positions.into_iter().for_each(|i| self.buckets.set(i + offset, 1))
+    }
+
+    pub fn association<T: Hash>(&self, item: &T) -> Association {
+        let (positions, offset) = self.positions(item);
+        let in_a = positions.iter().all(|i| self.buckets.get(*i) == 1);
+        let in_b = positions.iter().all(|i| self.buckets.get(i + offset) == 1);
+        match (in_a, in_b) {
+            (true, true) => Association::Both,
+            (true, false) => Association::A,
+            (false, true) => Association::B,
+            (false, false) => Association::None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.reset()
+    }
+
+    // returns the k positions and the offset of set B
+    fn positions<T: Hash>(&self, item: &T) -> (Vec<usize>, usize) {
+        let mut positions = self.hash_kernels.hash_iter(item).collect::<Vec<_>>();
+        let offset = positions.pop().unwrap_or(0) % MAX_OFFSET + 1;
+        (positions, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _association(a: &[usize], b: &[usize]) {
+        let mut filter = Filter::new(100, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        a.iter().for_each(|i| filter.insert_a(i));
+        b.iter().for_each(|i| filter.insert_b(i));
+        assert!(a
+            .iter()
+            .all(|i| matches!(filter.association(i), Association::A | Association::Both)));
+        assert!(b
+            .iter()
+            .all(|i| matches!(filter.association(i), Association::B | Association::Both)));
+    }
+
+    proptest! {
+        #[test]
+        fn association(
+            ref a in any_with::<Vec<usize>>(size_range(16).lift()),
+            ref b in any_with::<Vec<usize>>(size_range(16).lift())
+        ) {
+            _association(a, b)
+        }
+    }
+
+    #[test]
+    fn both() {
+        let mut filter = Filter::new(100, 0.001, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        filter.insert_a(&"a");
+        filter.insert_b(&"b");
+        filter.insert_a(&"both");
+        filter.insert_b(&"both");
+        assert_eq!(Association::A, filter.association(&"a"));
+        assert_eq!(Association::B, filter.association(&"b"));
+        assert_eq!(Association::Both, filter.association(&"both"));
+        assert_eq!(Association::None, filter.association(&"none"));
+    }
+}
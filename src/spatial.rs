@@ -0,0 +1,140 @@
+use crate::buckets::Buckets;
+use crate::hash::compute_k_num;
+use crate::{BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+/// Spatial Bloom Filter, stores items of disjoint sets labelled from 1 to
+/// labels. Inserting an item sets its buckets to the maximum of their value
+/// and the item's label, so the highest label wins on collisions, and a query
+/// returns the minimum value of the item's buckets.
+pub struct Filter<BHK: BuildHashKernels> {
+    buckets: Buckets,      // labels, 0 means empty
+    hash_kernels: BHK::HK, // hash kernels
+    k: usize,              // numbers of hash iterating
+    inserted: Vec<usize>,  // number of inserts of each label, indexed by label
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new spatial bloom filter structure.
+    /// items_count is an estimation of the maximum number of items of all sets.
+    /// labels is the number of sets, in [1, 127]
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(items_count: usize, labels: u8, fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        debug_assert!(labels > 0 && labels < 128);
+        let bucket_size = (8 - labels.leading_zeros()) as u8;
+        let buckets = Buckets::with_fp_rate(items_count, fp_rate, bucket_size);
+        let k = compute_k_num(fp_rate);
+        let hash_kernels = build_hash_kernels.with_k(k, buckets.len());
+        Self {
+            buckets,
+            hash_kernels,
+            k,
+            inserted: vec![0; labels as usize + 1],
+        }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    pub fn labels(&self) -> u8 {
+        (self.inserted.len() - 1) as u8
+    }
+
+    pub fn insert<T: Hash>(&mut self, item: &T, label: u8) {
+        debug_assert!(label > 0 && label <= self.labels());
+        let buckets = &mut self.buckets;
+        self.hash_kernels.hash_iter(item).for_each(|i| {
+            if buckets.get(i) < label {
+                buckets.set(i, label)
+            }
+        });
+        self.inserted[label as usize] += 1;
+    }
+
+    /// Returns the label of item, or `None` if item is not in any set.
+    pub fn label_of<T: Hash>(&self, item: &T) -> Option<u8> {
+        self.hash_kernels
+            .hash_iter(item)
+            .map(|i| self.buckets.get(i))
+            .min()
+            .filter(|label| *label > 0)
+    }
+
+    /// Returns the ratio of the buckets holding label to the buckets label
+    /// would hold without being overwritten by higher labels. Items of a set
+    /// with a low emersion are likely to be reported with a higher label.
+    pub fn emersion(&self, label: u8) -> f64 {
+        let m = self.buckets.len() as f64;
+        let inserts = (self.k * self.inserted[label as usize]) as f64;
+        let expected = m * (1.0 - (1.0 - 1.0 / m).powf(inserts));
+        if expected == 0.0 {
+            return 0.0;
+        }
+        self.bucket_counts()[label as usize] as f64 / expected
+    }
+
+    /// Returns the estimated probability that an item not in any set is
+    /// reported with label.
+    pub fn fp_rate(&self, label: u8) -> f64 {
+        let counts = self.bucket_counts();
+        let m = self.buckets.len() as f64;
+        let k = self.k as i32;
+        // fraction of buckets holding at least label and label + 1
+        let at_least = counts[label as usize..].iter().sum::<usize>() as f64 / m;
+        let above = counts[label as usize + 1..].iter().sum::<usize>() as f64 / m;
+        at_least.powi(k) - above.powi(k)
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets.reset();
+        self.inserted.iter_mut().for_each(|x| *x = 0)
+    }
+
+    // number of buckets holding each label, indexed by label
+    fn bucket_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.inserted.len()];
+        (0..self.buckets.len()).for_each(|i| counts[self.buckets.get(i) as usize] += 1);
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _label_of(items: &[(usize, u8)]) {
+        let mut filter = Filter::new(100, 3, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        let items = items.iter().map(|(i, label)| (*i, label % 3 + 1)).collect::<Vec<_>>();
+        items.iter().for_each(|(i, label)| filter.insert(i, *label));
+        // an item is never reported with a lower label
+        assert!(items.iter().all(|(i, label)| filter.label_of(i) >= Some(*label)));
+    }
+
+    proptest! {
+        #[test]
+        fn label_of(ref items in any_with::<Vec<(usize, u8)>>(size_range(32).lift())) {
+            _label_of(items)
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let mut filter = Filter::new(100, 3, 0.001, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        assert_eq!(3, filter.buckets().max_value());
+        assert_eq!(None, filter.label_of(&"allow"));
+        filter.insert(&"allow", 1);
+        filter.insert(&"deny", 3);
+        filter.insert(&"grey", 2);
+        assert_eq!(Some(1), filter.label_of(&"allow"));
+        assert_eq!(Some(2), filter.label_of(&"grey"));
+        assert_eq!(Some(3), filter.label_of(&"deny"));
+        assert!(filter.emersion(3) > 0.99);
+        assert!(filter.fp_rate(1) < 0.001);
+        assert!(filter.fp_rate(3) < 0.001);
+    }
+}
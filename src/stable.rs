@@ -1,8 +1,17 @@
 use crate::buckets::Buckets;
-use crate::hash::compute_k_num;
+use crate::format::{self, Header, DEFAULT_HASH_KERNELS, STABLE};
+use crate::go::{self, BuildFnv1Hasher};
+#[cfg(feature = "serde")]
+use crate::hash::DefaultHashKernels;
+use crate::hash::{compute_k_num, DefaultBuildHashKernels, DefaultBuildHasher};
 use crate::{BloomFilter, BuildHashKernels, HashKernels};
 use rand::random;
+#[cfg(feature = "serde")]
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::io::{Read, Write};
 
 pub struct Filter<BHK: BuildHashKernels> {
     buckets: Buckets,      // filter data
@@ -40,6 +49,68 @@ impl<BHK: BuildHashKernels> Filter<BHK> {
     }
 }
 
+impl Filter<DefaultBuildHashKernels<DefaultBuildHasher>> {
+    /// Writes the filter in the binary format of [`format`](crate::format).
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), format::Error> {
+        let header = Header {
+            kind: STABLE,
+            hash_kernels: DEFAULT_HASH_KERNELS,
+            bucket_size: self.buckets.bucket_size(),
+            count: self.buckets.len(),
+            k: self.hash_kernels.k(),
+            hash_seed: self.hash_kernels.hash_seed(),
+            p: self.p,
+        };
+        format::write(writer, &header, &self.buckets.raw_data())
+    }
+
+    /// Reads a filter written by `write_to`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, format::Error> {
+        let (header, data) = format::read(reader, STABLE)?;
+        if header.p == 0 {
+            return Err(format::Error::Inconsistent("stable bloom filter p must be positive"));
+        }
+        let buckets = Buckets::with_raw_data(header.count, header.bucket_size, &data);
+        let build_hash_kernels = DefaultBuildHashKernels::new(header.hash_seed, DefaultBuildHasher);
+        let hash_kernels = build_hash_kernels.with_k(header.k, buckets.len());
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            p: header.p,
+        })
+    }
+}
+
+impl Filter<DefaultBuildHashKernels<BuildFnv1Hasher>> {
+    /// Writes the filter in the format of `StableBloomFilter.WriteTo` of Go BoomFilters.
+    pub fn write_go<W: Write>(&self, mut writer: W) -> Result<(), format::Error> {
+        if self.hash_kernels.hash_seed() != 0 {
+            return Err(format::Error::Inconsistent("hash seed must be 0"));
+        }
+        go::write_u64(&mut writer, self.buckets.len())?;
+        go::write_u64(&mut writer, self.p)?;
+        go::write_u64(&mut writer, self.hash_kernels.k())?;
+        go::write_buckets(&mut writer, &self.buckets)
+    }
+
+    /// Reads a filter written by `StableBloomFilter.WriteTo` of Go BoomFilters.
+    pub fn read_go<R: Read>(mut reader: R) -> Result<Self, format::Error> {
+        let m = go::read_u64(&mut reader)?;
+        let p = go::read_u64(&mut reader)?;
+        let k = go::read_u64(&mut reader)?;
+        let buckets = go::read_buckets(&mut reader)?;
+        if buckets.len() != m || k == 0 || p == 0 {
+            return Err(format::Error::Inconsistent("stable bloom filter m, p, k or buckets mismatch"));
+        }
+        let hash_kernels = DefaultBuildHashKernels::new(0, BuildFnv1Hasher).with_k(k, m);
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            p,
+        })
+    }
+}
+
 // returns the optimal number of buckets to decrement, p, per
 // iteration for the provided parameters of an SBF.
 fn compute_p_num(m: usize, k: usize, d: u8, fp_rate: f64) -> usize {
@@ -71,6 +142,47 @@ impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<BH: BuildHasher + Serialize> Serialize for Filter<DefaultBuildHashKernels<BH>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("StableBloomFilter", 3)?;
+        state.serialize_field("buckets", &self.buckets)?;
+        state.serialize_field("hash_kernels", &self.hash_kernels)?;
+        state.serialize_field("p", &self.p)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, BH: BuildHasher + Deserialize<'de>> Deserialize<'de> for Filter<DefaultBuildHashKernels<BH>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "StableBloomFilter")]
+        struct Data<BH> {
+            buckets: Buckets,
+            hash_kernels: DefaultHashKernels<BH>,
+            p: usize,
+        }
+
+        let Data {
+            buckets,
+            hash_kernels,
+            p,
+        } = Data::deserialize(deserializer)?;
+        if hash_kernels.len() != buckets.len() {
+            return Err(D::Error::custom("hash kernels size doesn't match the buckets count"));
+        }
+        if p == 0 {
+            return Err(D::Error::custom("stable bloom filter p must be positive"));
+        }
+        Ok(Self {
+            buckets,
+            hash_kernels,
+            p,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,4 +205,81 @@ mod tests {
             _contains(items)
         }
     }
+
+    fn _write_to(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        let mut filter = Filter::new(100, 3, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        let filter = Filter::read_from(&bytes[..]).unwrap();
+        assert_eq!(filter.p, compute_p_num(100, 6, 3, 0.03));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn write_to(ref items in any_with::<Vec<usize>>(size_range(7).lift())) {
+            _write_to(items)
+        }
+    }
+
+    // written as BoomFilters writes "alpha", built from the Go source by
+    // hand until replaced by the output of testdata/boomfilters/main.go
+    const GO_STABLE: &str = "000000000000001000000000000000030000000000000002\
+                             0203000000000000001000000000000000040300c000";
+
+    #[test]
+    fn go() {
+        use crate::go::GoBytes;
+
+        let bytes = (0..GO_STABLE.len() / 2)
+            .map(|i| u8::from_str_radix(&GO_STABLE[i * 2..i * 2 + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        let filter = Filter::read_go(&bytes[..]).unwrap();
+        assert_eq!(3, filter.p);
+        assert!(filter.contains(&GoBytes(b"alpha")));
+        assert!(!filter.contains(&GoBytes(b"beta")));
+
+        let mut written = Vec::new();
+        filter.write_go(&mut written).unwrap();
+        assert_eq!(bytes, written);
+    }
+
+    #[cfg(feature = "serde")]
+    fn _serde(items: &[usize]) {
+        use crate::hash::DefaultBuildHasher;
+
+        let mut filter = Filter::new(100, 3, 0.03, DefaultBuildHashKernels::new(random(), DefaultBuildHasher));
+        items.iter().for_each(|i| filter.insert(i));
+        let json = serde_json::to_string(&filter).unwrap();
+        let filter: Filter<DefaultBuildHashKernels<DefaultBuildHasher>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(filter.p, compute_p_num(100, 6, 3, 0.03));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    #[cfg(feature = "serde")]
+    proptest! {
+        #[test]
+        fn serde(ref items in any_with::<Vec<usize>>(size_range(7).lift())) {
+            _serde(items)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_inconsistent() {
+        use crate::hash::DefaultBuildHasher;
+
+        let json = |p| {
+            format!(
+                r#"{{"buckets":{{"count":64,"bucket_size":3,"data":[0,0,0]}},"hash_kernels":{{"k":3,"n":64,"hash_seed":0,"build_hasher":null}},"p":{}}}"#,
+                p
+            )
+        };
+        type F = Filter<DefaultBuildHashKernels<DefaultBuildHasher>>;
+        assert!(serde_json::from_str::<F>(&json(2)).is_ok());
+        assert!(serde_json::from_str::<F>(&json(0)).is_err());
+    }
 }
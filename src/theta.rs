@@ -0,0 +1,261 @@
+use crate::format::{to_usize, Error};
+use std::collections::BTreeSet;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem::size_of;
+
+// theta is the fraction theta / 2 ^ 64 of the hash space
+const HASH_SPACE: f64 = 18_446_744_073_709_551_616.0;
+const BYTES_PER_HASH: usize = size_of::<u64>();
+
+/// K-Minimum-Values sketch, retains the hashes below theta, theta is lowered
+/// so that at most k hashes are retained. Unlike HyperLogLog, sketches can be
+/// intersected and subtracted.
+pub struct Sketch<BH: BuildHasher + Clone> {
+    hashes: BTreeSet<u64>, // retained hashes, all below theta
+    theta: u64,            // u64::MAX means the sketch is in exact mode
+    k: usize,              // nominal entries
+    hash_seed: usize,      // seed offset for anonymity and privacy purpose
+    build_hasher: BH,
+}
+
+impl<BH: BuildHasher + Clone> Sketch<BH> {
+    /// Creates a new sketch retaining at most k hashes, the relative standard
+    /// error of the estimation is about 1 / sqrt(k).
+    pub fn new(k: usize, hash_seed: usize, build_hasher: BH) -> Self {
+        debug_assert!(k > 0);
+        Self {
+            hashes: BTreeSet::new(),
+            theta: u64::MAX,
+            k,
+            hash_seed,
+            build_hasher,
+        }
+    }
+
+    /// Restores a sketch from its compact form, see `raw_data`. Hashes above
+    /// k are trimmed as `add` does.
+    pub fn with_raw_data(raw_data: &[u8], hash_seed: usize, build_hasher: BH) -> Result<Self, Error> {
+        if raw_data.len() < 2 * BYTES_PER_HASH || raw_data.len() % BYTES_PER_HASH != 0 {
+            return Err(Error::Inconsistent("raw data must be k, theta and hashes of 8 bytes"));
+        }
+        let mut words = raw_data.chunks(BYTES_PER_HASH).map(|chunk| {
+            let mut bytes = [0u8; BYTES_PER_HASH];
+            bytes.copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        });
+        let k = to_usize(words.next().unwrap_or(0))?;
+        if k == 0 {
+            return Err(Error::Inconsistent("k must be positive"));
+        }
+        let theta = words.next().unwrap_or(u64::MAX);
+        let mut sketch = Self {
+            hashes: words.filter(|h| *h < theta).collect(),
+            theta,
+            k,
+            hash_seed,
+            build_hasher,
+        };
+        sketch.trim();
+        Ok(sketch)
+    }
+
+    /// Returns the compact form of the sketch: k and theta followed by the
+    /// retained hashes in ascending order, all as little endian u64.
+    pub fn raw_data(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity((self.hashes.len() + 2) * BYTES_PER_HASH);
+        result.extend_from_slice(&(self.k as u64).to_le_bytes());
+        result.extend_from_slice(&self.theta.to_le_bytes());
+        self.hashes.iter().for_each(|h| result.extend_from_slice(&h.to_le_bytes()));
+        result
+    }
+
+    pub fn add<T: Hash>(&mut self, item: &T) {
+        let hasher = &mut self.build_hasher.build_hasher();
+        (self.hash_seed, item).hash(hasher);
+        let hash = hasher.finish();
+        if hash < self.theta && self.hashes.insert(hash) {
+            self.trim();
+        }
+    }
+
+    pub fn estimate(&self) -> f64 {
+        if self.is_exact() {
+            self.hashes.len() as f64
+        } else {
+            self.hashes.len() as f64 / self.theta_fraction()
+        }
+    }
+
+    /// Returns the lower and upper bounds of the estimation at the given
+    /// number of standard deviations.
+    pub fn bounds(&self, std_devs: f64) -> (f64, f64) {
+        let count = self.hashes.len() as f64;
+        if self.is_exact() {
+            return (count, count);
+        }
+        let p = self.theta_fraction();
+        let estimate = count / p;
+        let deviation = std_devs * (count * (1.0 - p)).sqrt() / p;
+        ((estimate - deviation).max(count), estimate + deviation)
+    }
+
+    pub fn theta(&self) -> u64 {
+        self.theta
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let theta = self.common_theta(other);
+        let mut result = self.with_hashes(
+            self.hashes.union(&other.hashes).filter(|h| **h < theta),
+            theta,
+            self.k.min(other.k),
+        );
+        result.trim();
+        result
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let theta = self.common_theta(other);
+        self.with_hashes(self.hashes.intersection(&other.hashes).filter(|h| **h < theta), theta, self.k)
+    }
+
+    /// Returns a sketch of the items of self which are not in other.
+    pub fn a_not_b(&self, other: &Self) -> Self {
+        let theta = self.common_theta(other);
+        self.with_hashes(self.hashes.difference(&other.hashes).filter(|h| **h < theta), theta, self.k)
+    }
+
+    pub fn reset(&mut self) {
+        self.hashes.clear();
+        self.theta = u64::MAX;
+    }
+
+    fn is_exact(&self) -> bool {
+        self.theta == u64::MAX
+    }
+
+    fn theta_fraction(&self) -> f64 {
+        self.theta as f64 / HASH_SPACE
+    }
+
+    fn common_theta(&self, other: &Self) -> u64 {
+        assert_eq!(self.hash_seed, other.hash_seed, "sketches must have the same hash seed");
+        self.theta.min(other.theta)
+    }
+
+    fn with_hashes<'a, I: Iterator<Item = &'a u64>>(&self, hashes: I, theta: u64, k: usize) -> Self {
+        Self {
+            hashes: hashes.cloned().collect(),
+            theta,
+            k,
+            hash_seed: self.hash_seed,
+            build_hasher: self.build_hasher.clone(),
+        }
+    }
+
+    // drops the largest hashes until at most k are retained, theta becomes the last dropped one
+    fn trim(&mut self) {
+        while self.hashes.len() > self.k {
+            if let Some(max) = self.hashes.iter().next_back().cloned() {
+                self.hashes.remove(&max);
+                self.theta = max;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHasher;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::HashSet;
+
+    fn _estimate(items: &[usize]) {
+        let mut sketch = Sketch::new(1024, random(), DefaultBuildHasher);
+        items.iter().for_each(|i| sketch.add(i));
+        let count = items.iter().collect::<HashSet<_>>().len() as f64;
+        assert_eq!(count, sketch.estimate());
+        assert_eq!((count, count), sketch.bounds(2.0));
+    }
+
+    proptest! {
+        #[test]
+        fn estimate(ref items in any_with::<Vec<usize>>(size_range(64).lift())) {
+            _estimate(items)
+        }
+    }
+
+    fn _raw_data(items: &[usize]) {
+        let hash_seed = random();
+        let mut sketch = Sketch::new(16, hash_seed, DefaultBuildHasher);
+        items.iter().for_each(|i| sketch.add(i));
+        let restored = Sketch::with_raw_data(&sketch.raw_data(), hash_seed, DefaultBuildHasher).unwrap();
+        assert_eq!(sketch.theta(), restored.theta());
+        assert_eq!(sketch.hashes, restored.hashes);
+        assert_eq!(sketch.raw_data(), restored.raw_data());
+    }
+
+    proptest! {
+        #[test]
+        fn raw_data(ref items in any_with::<Vec<usize>>(size_range(64).lift())) {
+            _raw_data(items)
+        }
+    }
+
+    #[test]
+    fn raw_data_errors() {
+        let sketch = |words: &[u64]| {
+            let bytes = words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect::<Vec<_>>();
+            Sketch::with_raw_data(&bytes, 0, DefaultBuildHasher)
+        };
+        assert!(matches!(sketch(&[1]), Err(Error::Inconsistent(_))));
+        assert!(matches!(sketch(&[0, u64::MAX]), Err(Error::Inconsistent(_))));
+        assert!(matches!(
+            Sketch::with_raw_data(&[0; 17], 0, DefaultBuildHasher),
+            Err(Error::Inconsistent(_))
+        ));
+
+        // more hashes than k are trimmed and lower theta
+        let restored = sketch(&[2, u64::MAX, 1, 2, 3, 4]).unwrap();
+        assert_eq!((3, 2), (restored.theta(), restored.hashes.len()));
+    }
+
+    #[test]
+    fn estimation_mode() {
+        let mut sketch = Sketch::new(4096, random(), DefaultBuildHasher);
+        (0..100_000).for_each(|i| sketch.add(&i));
+        assert_eq!(4096, sketch.hashes.len());
+        let (lower, upper) = sketch.bounds(4.0);
+        assert!(lower < 100_000.0 && 100_000.0 < upper);
+        assert!((sketch.estimate() - 100_000.0).abs() < 8_000.0);
+    }
+
+    #[test]
+    fn set_operations() {
+        let hash_seed = random();
+        let mut a = Sketch::new(4096, hash_seed, DefaultBuildHasher);
+        let mut b = Sketch::new(4096, hash_seed, DefaultBuildHasher);
+        (0..60_000).for_each(|i| a.add(&i));
+        (40_000..100_000).for_each(|i| b.add(&i));
+
+        assert!((a.union(&b).estimate() - 100_000.0).abs() < 8_000.0);
+        assert!((a.intersection(&b).estimate() - 20_000.0).abs() < 3_000.0);
+        assert!((a.a_not_b(&b).estimate() - 40_000.0).abs() < 4_000.0);
+
+        // exact mode
+        let mut c = Sketch::new(4096, hash_seed, DefaultBuildHasher);
+        let mut d = Sketch::new(4096, hash_seed, DefaultBuildHasher);
+        (0..10).for_each(|i| c.add(&i));
+        (5..20).for_each(|i| d.add(&i));
+        assert_eq!(20.0, c.union(&d).estimate());
+        assert_eq!(5.0, c.intersection(&d).estimate());
+        assert_eq!(5.0, c.a_not_b(&d).estimate());
+        assert_eq!(10.0, d.a_not_b(&c).estimate());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::classic::Filter as ClassicFilter;
+use crate::frequency::Sketch as FrequencySketch;
+use crate::{BloomFilter, BuildHashKernels};
+use std::hash::Hash;
+
+// 4 bits counters as in the TinyLFU paper
+const COUNTER_SIZE: u8 = 4;
+
+/// TinyLFU cache admission policy: the first access of an item within a sample
+/// only sets the doorkeeper, further accesses are counted by the frequency
+/// sketch. After sample_size accesses the doorkeeper is cleared and the
+/// counters are halved.
+pub struct Policy<BHK: BuildHashKernels> {
+    doorkeeper: ClassicFilter<BHK>, // items accessed once in the sample
+    sketch: FrequencySketch<BHK>,   // frequency of items accessed more than once
+    sample_size: usize,             // accesses between two resets
+    accesses: usize,                // accesses since the last reset
+}
+
+impl<BHK: BuildHashKernels + Clone> Policy<BHK> {
+    /// Create a new TinyLFU policy.
+    /// sample_size is the number of accesses between two resets, usually a
+    /// multiple of the cache size.
+    /// fp_rate is the wanted rate of false positives of the doorkeeper and the sketch, in ]0.0, 1.0[
+    pub fn new(sample_size: usize, fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        debug_assert!(sample_size > 0);
+        Self {
+            doorkeeper: ClassicFilter::new(sample_size, fp_rate, build_hash_kernels.clone()),
+            sketch: FrequencySketch::new(sample_size, COUNTER_SIZE, fp_rate, 0, build_hash_kernels),
+            sample_size,
+            accesses: 0,
+        }
+    }
+}
+
+impl<BHK: BuildHashKernels> Policy<BHK> {
+    pub fn record_access<T: Hash>(&mut self, item: &T) {
+        if self.doorkeeper.contains(item) {
+            self.sketch.insert(item);
+        } else {
+            self.doorkeeper.insert(item);
+        }
+
+        self.accesses += 1;
+        if self.accesses >= self.sample_size {
+            self.age();
+        }
+    }
+
+    /// Returns the estimated frequency of item in the current sample.
+    pub fn estimate<T: Hash>(&self, item: &T) -> usize {
+        let doorkeeper = if self.doorkeeper.contains(item) { 1 } else { 0 };
+        self.sketch.estimate(item) as usize + doorkeeper
+    }
+
+    /// Returns whether candidate should replace the eviction victim.
+    pub fn admit<T: Hash>(&self, candidate: &T, victim: &T) -> bool {
+        self.estimate(candidate) > self.estimate(victim)
+    }
+
+    /// Clears the doorkeeper and halves the counters, it is triggered every
+    /// `sample_size` accesses.
+    pub fn age(&mut self) {
+        self.doorkeeper.reset();
+        self.sketch.decay();
+        self.accesses /= 2;
+    }
+
+    pub fn reset(&mut self) {
+        self.doorkeeper.reset();
+        self.sketch.reset();
+        self.accesses = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn admit() {
+        let mut policy = Policy::new(1000, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        (0..5).for_each(|_| policy.record_access(&"hot"));
+        policy.record_access(&"cold");
+        assert_eq!(5, policy.estimate(&"hot"));
+        assert_eq!(1, policy.estimate(&"cold"));
+        assert!(policy.admit(&"hot", &"cold"));
+        assert!(!policy.admit(&"cold", &"hot"));
+        assert!(!policy.admit(&"unknown", &"cold"));
+    }
+
+    #[test]
+    fn age() {
+        let mut policy = Policy::new(10, 0.01, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        (0..9).for_each(|_| policy.record_access(&"item"));
+        assert_eq!(9, policy.estimate(&"item"));
+        // the 10th access clears the doorkeeper and halves the counters
+        policy.record_access(&"item");
+        assert_eq!(4, policy.estimate(&"item"));
+    }
+}
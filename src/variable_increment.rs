@@ -0,0 +1,134 @@
+use crate::buckets::Buckets;
+use crate::{BloomFilter, BuildHashKernels, HashKernels, RemovableBloomFilter};
+use std::hash::Hash;
+
+/// Variable-increment counting bloom filter (VI-CBF), every hash function
+/// chooses a bucket and an increment in the set D_L = [L, 2L - 1], so a query
+/// can tell apart the counters which cannot be a sum of its increment and
+/// other increments of D_L. Saturated counters are never decremented.
+pub struct Filter<BHK: BuildHashKernels> {
+    buckets: Buckets,      // counters
+    hash_kernels: BHK::HK, // hash kernels of buckets count * L values
+    l: usize,              // smallest increment, increments are in [L, 2L - 1]
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new variable-increment counting bloom filter structure.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// bucket_size is the specified number of bits, in [2, 7], L is 2 ^ (bucket_size - 2)
+    /// fp_rate is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new(items_count: usize, bucket_size: u8, fp_rate: f64, build_hash_kernels: BHK) -> Self {
+        debug_assert!(bucket_size >= 2);
+        let buckets = Buckets::with_fp_rate(items_count, fp_rate, bucket_size);
+        let l = 1 << (bucket_size - 2);
+        let hash_kernels = build_hash_kernels.with_fp_rate(fp_rate, buckets.len() * l);
+        Self {
+            buckets,
+            hash_kernels,
+            l,
+        }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    // returns the buckets and increments of item
+    fn positions<'a, T: Hash>(&'a self, item: &T) -> impl Iterator<Item = (usize, u8)> + 'a {
+        let m = self.buckets.len();
+        let l = self.l;
+        self.hash_kernels.hash_iter(item).map(move |h| (h % m, (l + h / m) as u8))
+    }
+}
+
+impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
+    fn insert<T: Hash>(&mut self, item: &T) {
+        let positions = self.positions(item).collect::<Vec<_>>();
+        let max = self.buckets.max_value();
+        for (i, increment) in positions {
+            let value = self.buckets.get(i);
+            self.buckets.set(i, value.saturating_add(increment).min(max))
+        }
+    }
+
+    fn contains<T: Hash>(&self, item: &T) -> bool {
+        let max = self.buckets.max_value();
+        let l = self.l as u8;
+        self.positions(item).all(|(i, increment)| {
+            let value = self.buckets.get(i);
+            // the rest of a counter is 0 or a sum of increments, all at least L
+            value == max || value == increment || (value > increment && value - increment >= l)
+        })
+    }
+
+    fn reset(&mut self) {
+        self.buckets.reset()
+    }
+}
+
+impl<BHK: BuildHashKernels> RemovableBloomFilter for Filter<BHK> {
+    fn remove<T: Hash>(&mut self, item: &T) {
+        let positions = self.positions(item).collect::<Vec<_>>();
+        let max = self.buckets.max_value();
+        for (i, increment) in positions {
+            let value = self.buckets.get(i);
+            if value < max {
+                self.buckets.set(i, value.saturating_sub(increment))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _contains(items: &[usize]) {
+        let mut filter = Filter::new(100, 4, 0.03, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        assert!(items.iter().all(|i| !filter.contains(i)));
+        items.iter().for_each(|i| filter.insert(i));
+        assert!(items.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<usize>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    fn _remove(item: usize) {
+        // with 2 hash functions, increments of at most 7 can't saturate a
+        // counter of 4 bits, which would never be decremented
+        let mut filter = Filter::new(100, 4, 0.25, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        filter.insert(&item);
+        filter.remove(&item);
+        assert!(!filter.contains(&item));
+    }
+
+    proptest! {
+        #[test]
+        fn remove(items in any::<usize>()) {
+            _remove(items)
+        }
+    }
+
+    fn _remove_some(items: &[usize]) {
+        let mut filter = Filter::new(100, 4, 0.03, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        items.iter().for_each(|i| filter.insert(i));
+        let (removed, kept) = items.split_at(items.len() / 2);
+        removed.iter().for_each(|i| filter.remove(i));
+        assert!(kept.iter().all(|i| filter.contains(i)));
+    }
+
+    proptest! {
+        #[test]
+        fn remove_some(ref items in any_with::<Vec<usize>>(size_range(32).lift())) {
+            _remove_some(items)
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::buckets::Buckets;
+use crate::hash::compute_k_num;
+use crate::{BloomFilter, BuildHashKernels, HashKernels};
+use std::hash::Hash;
+
+/// Weighted Bloom filter, every item uses its own number of hash functions so
+/// that items queried often, or whose false positives are costly, get a lower
+/// false positive rate. An item must be queried with the k it was inserted with.
+pub struct Filter<BHK: BuildHashKernels> {
+    buckets: Buckets,      // filter data
+    hash_kernels: BHK::HK, // hash kernels of max_k hash functions
+    k: usize,              // numbers of hash iterating of an item of cost 1.0
+    max_k: usize,          // max numbers of hash iterating of an item
+}
+
+impl<BHK: BuildHashKernels> Filter<BHK> {
+    /// Create a new weighted bloom filter structure.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// fp_rate is the wanted rate of false positives of items of cost 1.0, in ]0.0, 1.0[
+    /// max_k is the max number of hash functions of an item.
+    pub fn new(items_count: usize, fp_rate: f64, max_k: usize, build_hash_kernels: BHK) -> Self {
+        let buckets = Buckets::with_fp_rate(items_count, fp_rate, 1);
+        let k = compute_k_num(fp_rate).min(max_k);
+        let hash_kernels = build_hash_kernels.with_k(max_k, buckets.len());
+        Self {
+            buckets,
+            hash_kernels,
+            k,
+            max_k,
+        }
+    }
+
+    pub fn buckets(&self) -> &Buckets {
+        &self.buckets
+    }
+
+    /// Returns the number of hash functions of an item of the given relative
+    /// cost, items of cost 1.0 use the k optimized for fp_rate, doubling the
+    /// cost adds one hash function.
+    pub fn k_for_cost(&self, cost: f64) -> usize {
+        debug_assert!(cost > 0.0);
+        let k = (self.k as f64 + cost.log2()).round();
+        if k < 1.0 {
+            1
+        } else {
+            (k as usize).min(self.max_k)
+        }
+    }
+
+    pub fn insert_with_k<T: Hash>(&mut self, item: &T, k: usize) {
+        let buckets = &mut self.buckets;
+        self.hash_kernels.hash_iter(item).take(k).for_each(|i| buckets.set(i, 1))
+    }
+
+    pub fn contains_with_k<T: Hash>(&self, item: &T, k: usize) -> bool {
+        self.hash_kernels.hash_iter(item).take(k).all(|i| self.buckets.get(i) == 1)
+    }
+
+    pub fn insert_with_cost<T: Hash>(&mut self, item: &T, cost: f64) {
+        let k = self.k_for_cost(cost);
+        self.insert_with_k(item, k)
+    }
+
+    pub fn contains_with_cost<T: Hash>(&self, item: &T, cost: f64) -> bool {
+        self.contains_with_k(item, self.k_for_cost(cost))
+    }
+
+    /// Returns the false positive rate of an item of k hash functions with
+    /// the current buckets.
+    pub fn fp_rate(&self, k: usize) -> f64 {
+        self.fill_ratio().powi(k as i32)
+    }
+
+    /// Returns the expected false positive rate of a query distribution, given
+    /// as pairs of query weight and cost of the queried items.
+    pub fn weighted_fp_rate(&self, queries: &[(f64, f64)]) -> f64 {
+        let fill_ratio = self.fill_ratio();
+        let (fp, weights) = queries.iter().fold((0.0, 0.0), |(fp, weights), (weight, cost)| {
+            let k = self.k_for_cost(*cost) as i32;
+            (fp + weight * fill_ratio.powi(k), weights + weight)
+        });
+        if weights > 0.0 {
+            fp / weights
+        } else {
+            0.0
+        }
+    }
+
+    // fraction of the buckets which are set
+    fn fill_ratio(&self) -> f64 {
+        let set = (0..self.buckets.len()).filter(|i| self.buckets.get(*i) == 1).count();
+        set as f64 / self.buckets.len() as f64
+    }
+}
+
+impl<BHK: BuildHashKernels> BloomFilter for Filter<BHK> {
+    fn insert<T: Hash>(&mut self, item: &T) {
+        self.insert_with_k(item, self.k)
+    }
+
+    fn contains<T: Hash>(&self, item: &T) -> bool {
+        self.contains_with_k(item, self.k)
+    }
+
+    fn reset(&mut self) {
+        self.buckets.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DefaultBuildHashKernels;
+    use proptest::{collection::size_range, prelude::any_with, proptest};
+    use rand::random;
+    use std::collections::hash_map::RandomState;
+
+    fn _contains(items: &[(usize, u8)]) {
+        let mut filter = Filter::new(100, 0.03, 12, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        let items = items
+            .iter()
+            .map(|(i, cost)| (*i, f64::from(cost % 16 + 1) / 4.0))
+            .collect::<Vec<_>>();
+        items.iter().for_each(|(i, cost)| filter.insert_with_cost(i, *cost));
+        assert!(items.iter().all(|(i, cost)| filter.contains_with_cost(i, *cost)));
+    }
+
+    proptest! {
+        #[test]
+        fn contains(ref items in any_with::<Vec<(usize, u8)>>(size_range(16).lift())) {
+            _contains(items)
+        }
+    }
+
+    #[test]
+    fn k_for_cost() {
+        let filter = Filter::new(100, 0.03, 8, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        assert_eq!(6, filter.k_for_cost(1.0));
+        assert_eq!(7, filter.k_for_cost(2.0));
+        assert_eq!(8, filter.k_for_cost(16.0));
+        assert_eq!(5, filter.k_for_cost(0.5));
+        assert_eq!(1, filter.k_for_cost(0.001));
+    }
+
+    #[test]
+    fn weighted_fp_rate() {
+        let mut filter = Filter::new(100, 0.03, 12, DefaultBuildHashKernels::new(random(), RandomState::new()));
+        assert_eq!(0.0, filter.weighted_fp_rate(&[(1.0, 1.0)]));
+        (0..100).for_each(|i| filter.insert(&i));
+        let uniform = filter.weighted_fp_rate(&[(1.0, 1.0)]);
+        let skewed = filter.weighted_fp_rate(&[(9.0, 8.0), (1.0, 1.0)]);
+        assert!((uniform - filter.fp_rate(6)).abs() < f64::EPSILON);
+        assert!(skewed < uniform);
+    }
+}
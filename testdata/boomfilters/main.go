@@ -0,0 +1,77 @@
+// Command boomfilters prints the BoomFilters vectors of the `go` tests of
+// src/classic.rs, src/counting.rs and src/stable.rs:
+//
+//	cd testdata/boomfilters
+//	go mod init boomfilters && go get github.com/tylertreat/BoomFilters
+//	go run .
+//
+// The constructors of BoomFilters derive m and k from a false positive rate,
+// so the parameters of the vectors (m, k, p and the bucket size) are set by
+// reading empty filters with ReadFrom. Their buckets and counts are then
+// filled by Add and written by WriteTo.
+package main
+
+import (
+	"bytes"
+	"encoding/binary"
+	"encoding/hex"
+	"fmt"
+	"log"
+
+	boom "github.com/tylertreat/BoomFilters"
+)
+
+// empty returns the WriteTo bytes of a filter without items: the three
+// header fields followed by zeroed buckets.
+func empty(header [3]uint64, bucketSize uint8, count uint64) *bytes.Buffer {
+	var buf bytes.Buffer
+	for _, field := range header {
+		binary.Write(&buf, binary.BigEndian, field)
+	}
+	buf.Write([]byte{bucketSize, 1<<bucketSize - 1})
+	length := (count*uint64(bucketSize) + 7) / 8
+	binary.Write(&buf, binary.BigEndian, count)
+	binary.Write(&buf, binary.BigEndian, length)
+	buf.Write(make([]byte, length))
+	return &buf
+}
+
+func main() {
+	// count, m, k
+	classic := boom.NewBloomFilter(2, 0.2)
+	if _, err := classic.ReadFrom(empty([3]uint64{0, 64, 3}, 1, 64)); err != nil {
+		log.Fatal(err)
+	}
+	classic.Add([]byte("alpha"))
+	classic.Add([]byte("beta"))
+	var out bytes.Buffer
+	if _, err := classic.WriteTo(&out); err != nil {
+		log.Fatal(err)
+	}
+	fmt.Println("GO_CLASSIC", hex.EncodeToString(out.Bytes()))
+
+	// count, m, k
+	counting := boom.NewCountingBloomFilter(2, 4, 0.2)
+	if _, err := counting.ReadFrom(empty([3]uint64{0, 16, 3}, 4, 16)); err != nil {
+		log.Fatal(err)
+	}
+	counting.Add([]byte("alpha"))
+	counting.Add([]byte("beta"))
+	out.Reset()
+	if _, err := counting.WriteTo(&out); err != nil {
+		log.Fatal(err)
+	}
+	fmt.Println("GO_COUNTING", hex.EncodeToString(out.Bytes()))
+
+	// m, p, k, the decrements of Add don't change the zeroed buckets
+	stable := boom.NewStableBloomFilter(16, 2, 0.1)
+	if _, err := stable.ReadFrom(empty([3]uint64{16, 3, 2}, 2, 16)); err != nil {
+		log.Fatal(err)
+	}
+	stable.Add([]byte("alpha"))
+	out.Reset()
+	if _, err := stable.WriteTo(&out); err != nil {
+		log.Fatal(err)
+	}
+	fmt.Println("GO_STABLE", hex.EncodeToString(out.Bytes()))
+}